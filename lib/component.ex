@@ -105,7 +105,12 @@ defmodule Orange.Component do
 
   @type ui_element :: Orange.Rect.t()
   @type state :: map
-  @type event :: Orange.Terminal.KeyEvent.t()
+  @type event ::
+          Orange.Terminal.KeyEvent.t()
+          | Orange.Terminal.MouseEvent.t()
+          | Orange.Terminal.PasteEvent.t()
+          | Orange.Terminal.FocusEvent.t()
+          | Orange.Terminal.ClipboardEvent.t()
   @type update_callback :: (state -> state) | state
 
   @doc """
@@ -84,6 +84,12 @@ defmodule Orange.Component.Input do
         state = update_in(state, [:input], &(&1 <> char))
         {:update, state}
 
+      # The input is single line, so line breaks are dropped from the pasted text
+      %Orange.Terminal.PasteEvent{content: content} ->
+        content = String.replace(content, ["\r\n", "\r", "\n"], "")
+        state = update_in(state, [:input], &(&1 <> content))
+        {:update, state}
+
       %Orange.Terminal.KeyEvent{code: :backspace} ->
         state =
           if String.length(state.input) > 0,
@@ -262,7 +262,20 @@ defmodule Orange.Macro do
     * `:dim`
     * `:italic`
     * `:underline`
+    * `:double_underline`
+    * `:curly_underline`
+    * `:dotted_underline`
+    * `:dashed_underline`
+    * `:overline`
     * `:strikethrough`
+    * `:reverse` - swap the foreground and background colors
+    * `:hidden`
+    * `:slow_blink`
+    * `:rapid_blink`
+
+  The `:double_underline`, `:curly_underline`, `:dotted_underline`, `:dashed_underline` and `:overline` modifiers are not supported by every terminal. Terminals which don't support them usually ignore them or draw a single underline instead.
+
+  Drawing text with an unknown modifier raises an `ErlangError` with `{:invalid_text_modifier, modifier}`.
 
   ### Color
 
@@ -270,7 +283,9 @@ defmodule Orange.Macro do
 
     * `:background_color` - the color of the component background
 
-  The values for `:color` and `:background_color` is a single atom representing the color. Supported colors are:
+    * `:underline_color` - the color of the text underline. The value is inherited like `:color`. If not specified, the underline has the same color as the text
+
+  The values for `:color`, `:background_color` and `:underline_color` can be either a named color, an RGB color or an ANSI 256 color. Supported named colors are:
 
     * `:white`
     * `:black`
@@ -289,6 +304,26 @@ defmodule Orange.Macro do
     * `:cyan`
     * `:dark_cyan`
 
+  Other supported formats are:
+
+    * `{:rgb, r, g, b}` - a truecolor value, each channel in the range 0-255
+    * `{:ansi256, n}` - a color from the 256 colors palette
+    * `"#rrggbb"` - a hex string, equivalent to `{:rgb, r, g, b}`
+
+  If the terminal doesn't support truecolor, RGB colors are converted to the closest color it can display.
+
+  Drawing a cell with an unsupported color, e.g. an unknown named color or a channel out of the 0-255 range, raises an `ErlangError` with `{:invalid_color, description}`, where the description is the color as a string.
+
+  ### Hyperlink
+
+    * `:hyperlink` - makes the component text a hyperlink, which can be opened by clicking it in terminals supporting OSC 8. The value is either an URI, or an `{uri, id}` tuple. Terminals highlight text sharing the same id together on hover, e.g. a link wrapped over several lines
+
+      ```
+      rect style: [hyperlink: "https://github.com/Goose97/orange"] do
+        "Orange"
+      end
+      ```
+
   ### Line wrap
 
   By default, the text will wrap to the next line if it exceeds the width of the component. To disable this behavior, set the `:line_wrap` attribute to `false`
@@ -353,6 +388,10 @@ defmodule Orange.Macro do
 
       * `:background_color` - see [Color](#module-color) section
 
+      * `:underline_color` - see [Color](#module-color) section
+
+      * `:hyperlink` - see [Hyperlink](#module-hyperlink) section
+
       * `:scroll_bar` - see [Border](#module-border) section
 
       * `:display` - see [Display][#module-display] section
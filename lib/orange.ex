@@ -54,15 +54,47 @@ defmodule Orange do
   @doc """
   Start the runtime and render the UI root.
 
-  The root component must be a custom component.
+  The root component must be a custom component. Returns `{:error, reason}` if the runtime
+  can't be started, e.g. the terminal can't be switched to raw mode because stdout is not a TTY.
+
+  The UI is drawn to stdout by default. To keep stdout free, e.g. to print a result which is
+  piped to another program, draw the UI to the terminal instead:
+
+      config :orange, output: :tty
+
+  Supported outputs are `:stdout`, `:stderr` and `:tty`.
+
+  The UI takes the whole screen by default. Small UIs like prompts and progress bars can be
+  drawn inline instead, in a few lines under the cursor. When the runtime stops, the last
+  frame is left on the screen:
+
+      config :orange, viewport: {:inline, 5}
+
+  Mouse events are not reported by default, since capturing the mouse disables the
+  terminal's own text selection. To receive `Orange.Terminal.MouseEvent`s:
+
+      config :orange, mouse_capture: true
+
+  The session can be recorded to an
+  [asciicast v2](https://docs.asciinema.org/manual/asciicast/v2/) file, e.g. to attach it to a
+  bug report or to make a demo GIF with `agg`. The output and resizes are recorded, and the
+  input too with `input: true`:
+
+      config :orange, recording: "session.cast"
+      config :orange, recording: {"session.cast", input: true}
   """
   def start(element) do
-    {:ok, pid} = Orange.Runtime.start(element)
-    ref = Process.monitor(pid)
+    case Orange.Runtime.start(element) do
+      {:ok, pid} ->
+        ref = Process.monitor(pid)
 
-    # Wait for the runtime to stop with `stop/0`
-    receive do
-      {:DOWN, ^ref, :process, _pid, _reason} -> :ok
+        # Wait for the runtime to stop with `stop/0`
+        receive do
+          {:DOWN, ^ref, :process, _pid, _reason} -> :ok
+        end
+
+      {:error, reason} ->
+        {:error, reason}
     end
   end
 
@@ -140,6 +172,64 @@ defmodule Orange do
   """
   defdelegate get_layout_size(component_id), to: Orange.Runtime
 
+  @doc """
+  Copy the text to the clipboard. The target is either `:clipboard` (default) or `:primary`
+  for the primary selection.
+
+  The clipboard is set by the terminal with OSC 52, so it works over SSH too. Terminals which
+  don't support OSC 52 ignore it.
+  """
+  def copy_to_clipboard(text, target \\ :clipboard) do
+    terminal_impl = Application.get_env(:orange, :terminal, Orange.Terminal)
+    terminal_impl.copy_to_clipboard(text, target)
+  end
+
+  @doc """
+  Request the clipboard content. The target is either `:clipboard` (default) or `:primary`.
+
+  The content is dispatched as an `Orange.Terminal.ClipboardEvent` to the subscribed or focused
+  components, like other terminal events. Most terminals don't allow reading the clipboard by
+  default, or ask the user first. No event is dispatched if the terminal doesn't reply.
+  """
+  def request_clipboard(target \\ :clipboard) do
+    terminal_impl = Application.get_env(:orange, :terminal, Orange.Terminal)
+    terminal_impl.request_clipboard(Process.whereis(Orange.Runtime.RenderLoop), target)
+  end
+
+  @doc """
+  Set the terminal window title. The original title is restored when the runtime stops, if the
+  terminal supports saving titles.
+  """
+  def set_title(title) do
+    terminal_impl = Application.get_env(:orange, :terminal, Orange.Terminal)
+    terminal_impl.set_title(title)
+  end
+
+  @doc """
+  Suspend the application like Ctrl+Z does for other command line tools. The terminal is given
+  back to the shell until the process is continued, e.g. with `fg`, then the whole screen is
+  redrawn.
+
+  In raw mode, Ctrl+Z is received as a key event, so applications which support job control
+  call this function from their event handler:
+
+      @impl true
+      def handle_event(event, _state, _attrs, _update) do
+        case event do
+          %Orange.Terminal.KeyEvent{code: {:char, "z"}, modifiers: [:control]} ->
+            Orange.suspend()
+            :noop
+
+          _ ->
+            :noop
+        end
+      end
+  """
+  def suspend() do
+    terminal_impl = Application.get_env(:orange, :terminal, Orange.Terminal)
+    terminal_impl.suspend()
+  end
+
   @doc """
   Get the terminal size.
   """
@@ -147,4 +237,28 @@ defmodule Orange do
     terminal_impl = Application.get_env(:orange, :terminal, Orange.Terminal)
     terminal_impl.terminal_size()
   end
+
+  @doc """
+  Get the terminal default foreground and background colors, as `{:rgb, r, g, b}`. A color is
+  `nil` if the terminal doesn't report it within `timeout` milliseconds.
+
+  This is useful to pick a light or dark palette. It must be called before `start/1`, because
+  the terminal reply is read from the same input as the terminal events. The query is written
+  to the configured output, e.g. the terminal with `config :orange, output: :tty`.
+
+  ## Example
+
+      iex> Orange.terminal_colors()
+      %{foreground: {:rgb, 0, 0, 0}, background: {:rgb, 255, 255, 255}}
+  """
+  def terminal_colors(timeout \\ 100) do
+    terminal_impl = Application.get_env(:orange, :terminal, Orange.Terminal)
+
+    # The output is not set until the runtime starts, and the query must not end up in a
+    # piped stdout
+    case terminal_impl.set_output(Application.get_env(:orange, :output, :stdout)) do
+      :ok -> terminal_impl.terminal_colors(timeout)
+      {:error, _reason} -> %{foreground: nil, background: nil}
+    end
+  end
 end
@@ -406,6 +406,8 @@ defmodule Orange.Renderer do
         Buffer.write_string(buffer, coordinates, content.text, direction,
           background_color: content[:background_color],
           color: content[:color],
+          underline_color: content[:underline_color],
+          hyperlink: content[:hyperlink],
           text_modifiers: content[:text_modifiers] || []
         )
 
@@ -447,6 +449,8 @@ defmodule Orange.Renderer do
 
           opts = [
             color: get_in(attributes, [:style, :color]),
+            underline_color: get_in(attributes, [:style, :underline_color]),
+            hyperlink: get_in(attributes, [:style, :hyperlink]),
             text_modifiers: get_in(attributes, [:style, :text_modifiers]) || []
           ]
 
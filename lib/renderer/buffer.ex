@@ -56,6 +56,8 @@ defmodule Orange.Renderer.Buffer do
             character: char,
             foreground: opts[:color],
             background: opts[:background_color],
+            underline_color: opts[:underline_color],
+            hyperlink: opts[:hyperlink],
             modifiers: Keyword.get(opts, :text_modifiers, [])
           }
 
@@ -85,6 +87,8 @@ defmodule Orange.Renderer.Buffer do
         character: char,
         foreground: opts[:color],
         background: opts[:background_color],
+        underline_color: opts[:underline_color],
+        hyperlink: opts[:hyperlink],
         modifiers: Keyword.get(opts, :text_modifiers, [])
       }
 
@@ -220,6 +224,11 @@ defmodule Orange.Renderer.Buffer do
     cell.background
   end
 
+  def get_underline_color(%__MODULE__{} = buffer, x, y) do
+    cell = get_cell(buffer, {x, y})
+    cell.underline_color
+  end
+
   def get_modifiers(%__MODULE__{} = buffer, x, y) do
     cell = get_cell(buffer, {x, y})
     cell.modifiers
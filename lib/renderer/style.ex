@@ -48,16 +48,22 @@ defmodule Orange.Renderer.Style do
   end
 
   # Inherits style properties from parent style.
-  # Currently only inherits color and text_modifiers.
+  # Currently only inherits color, underline_color and text_modifiers.
   def inherit_style(style, nil), do: style
   def inherit_style(nil, parent_style), do: inherit_style([], parent_style)
 
   def inherit_style(style, parent_style) do
     parent_color = parent_style[:color]
+    parent_underline_color = parent_style[:underline_color]
     parent_text_modifiers = parent_style[:text_modifiers]
 
     style = if parent_color, do: Keyword.put_new(style, :color, parent_color), else: style
 
+    style =
+      if parent_underline_color,
+        do: Keyword.put_new(style, :underline_color, parent_underline_color),
+        else: style
+
     if parent_text_modifiers,
       do: Keyword.put_new(style, :text_modifiers, parent_text_modifiers),
       else: style
@@ -1,7 +1,14 @@
 defmodule Orange.Renderer.Cell do
   @moduledoc false
 
-  defstruct [:foreground, :background, modifiers: [], character: " "]
+  defstruct [
+    :foreground,
+    :background,
+    :underline_color,
+    :hyperlink,
+    modifiers: [],
+    character: " "
+  ]
 end
 
 defmodule Orange.Renderer.Box do
@@ -22,7 +29,12 @@ defmodule Orange.Renderer.Box do
             {top :: non_neg_integer, right :: non_neg_integer, bottom :: non_neg_integer,
              left :: non_neg_integer},
           border: {top :: boolean, right :: boolean, bottom :: boolean, left :: boolean} | nil,
-          style: [text_modifiers: [atom], background_color: :atom, color: :atom],
+          style: [
+            text_modifiers: [atom],
+            background_color: :atom,
+            color: :atom,
+            underline_color: :atom
+          ],
           width: non_neg_integer | nil,
           height: non_neg_integer | nil,
           layout_direction: :row | :column,
@@ -9,7 +9,7 @@ defmodule Orange.Runtime.EventManager do
     @callback unsubscribe(reference()) :: any()
     @callback focus(reference()) :: any()
     @callback unfocus(reference()) :: any()
-    @callback dispatch_event(Orange.Terminal.KeyEvent.t()) :: boolean()
+    @callback dispatch_event(Orange.Component.event()) :: boolean()
   end
 
   @state __MODULE__.State
@@ -1,6 +1,9 @@
 defmodule Orange.Runtime.EventPoller do
   @moduledoc false
 
+  # Owns the terminal event reader. The reader reads events in a native thread and sends them
+  # directly to the event receiver. It's stopped when the runtime shuts down.
+
   alias Orange.Terminal
 
   def child_spec(args) do
@@ -11,12 +14,20 @@ defmodule Orange.Runtime.EventPoller do
   end
 
   def start_link(params),
-    do: {:ok, spawn_link(fn -> poll_event_loop(params[:event_receiver]) end)}
+    do: {:ok, spawn_link(fn -> run(params[:event_receiver]) end)}
+
+  defp run(event_receiver) do
+    receiver = if is_pid(event_receiver), do: event_receiver, else: Process.whereis(event_receiver)
+    # Exit signals are trapped, so that the reader is stopped before the runtime restores the
+    # terminal instead of whenever the handle is garbage collected
+    Process.flag(:trap_exit, true)
+    reader = terminal_impl().start_event_reader(receiver)
 
-  defp poll_event_loop(event_receiver) do
-    event = terminal_impl().poll_event()
-    send(event_receiver, {:event, event})
-    poll_event_loop(event_receiver)
+    receive do
+      {:EXIT, _from, reason} ->
+        terminal_impl().stop_event_reader(reader)
+        exit(reason)
+    end
   end
 
   defp terminal_impl(), do: Application.get_env(:orange, :terminal, Terminal)
@@ -22,30 +22,83 @@ defmodule Orange.Runtime.RenderLoop do
   def init(root) do
     Runtime.ComponentRegistry.init()
 
-    terminal_impl().enter_alternate_screen()
-    terminal_impl().enable_raw_mode()
-    terminal_impl().hide_cursor()
-
-    state = %{
-      root: normalize_tree_node(root),
-      terminal_size: terminal_impl().terminal_size(),
-      previous_tree: nil,
-      previous_buffer: nil
-    }
-
-    Process.flag(:trap_exit, true)
-    {:ok, state, {:continue, :tick}}
+    case setup_terminal() do
+      {:ok, viewport} ->
+        # Must be probed before the event poller starts, which happens after this process
+        if terminal_impl().supports_synchronized_output(),
+          do: terminal_impl().set_synchronized_output(true)
+
+        state = %{
+          root: normalize_tree_node(root),
+          viewport: viewport,
+          terminal_size: viewport_size(terminal_impl().terminal_size(), viewport),
+          frame_buffer: terminal_impl().new_frame_buffer(),
+          previous_tree: nil
+        }
+
+        Process.flag(:trap_exit, true)
+        {:ok, state, {:continue, :tick}}
+
+      {:error, reason} ->
+        restore_terminal()
+        {:stop, {:terminal_error, reason}}
+    end
   end
 
   @impl true
   def terminate(_reason, _state) do
-    terminal_impl().leave_alternate_screen()
-    terminal_impl().disable_raw_mode()
-    terminal_impl().show_cursor()
-
+    restore_terminal()
     :ok
   end
 
+  defp setup_terminal() do
+    with :ok <- terminal_impl().set_output(Application.get_env(:orange, :output, :stdout)),
+         :ok <- start_recording(Application.get_env(:orange, :recording)),
+         {:ok, viewport} <- enter_viewport(viewport_config()),
+         :ok <- terminal_impl().enable_raw_mode(),
+         :ok <- terminal_impl().hide_cursor(),
+         :ok <- terminal_impl().enable_bracketed_paste(),
+         :ok <- enable_mouse_capture(mouse_capture?()),
+         # The title set by the application is reverted on exit
+         :ok <- terminal_impl().push_title(),
+         do: {:ok, viewport}
+  end
+
+  # Started before the terminal setup, so that the recording replays it
+  defp start_recording(nil), do: :ok
+  defp start_recording({path, opts}), do: terminal_impl().start_recording(path, opts)
+  defp start_recording(path), do: terminal_impl().start_recording(path, [])
+
+  # Off by default, since it disables the text selection of the terminal
+  defp enable_mouse_capture(true), do: terminal_impl().enable_mouse_capture()
+  defp enable_mouse_capture(false), do: :ok
+
+  defp enter_viewport(:fullscreen) do
+    with :ok <- terminal_impl().enter_alternate_screen(), do: {:ok, :fullscreen}
+  end
+
+  # The viewport height is capped to the terminal height
+  defp enter_viewport({:inline, height}) do
+    with {:ok, height} <- terminal_impl().enter_inline_viewport(height),
+         do: {:ok, {:inline, height}}
+  end
+
+  # Best effort, the terminal might be gone already. Undoes everything setup_terminal
+  # enabled, and leaves the last frame on the screen in inline mode. The recording is stopped
+  # after the terminal is restored, so that replaying it doesn't leave the player broken.
+  defp restore_terminal() do
+    if mouse_capture?(), do: terminal_impl().disable_mouse_capture()
+    terminal_impl().restore_terminal()
+    terminal_impl().stop_recording()
+  end
+
+  # The terminal can't be written to or read from anymore, e.g. stdout is a closed pipe.
+  # There is nothing left to render, so we shut down the runtime.
+  defp handle_terminal_error(reason) do
+    Logger.error("Terminal error: #{inspect(reason)}. Stopping the runtime")
+    Runtime.stop()
+  end
+
   # Walk the tree and normalize tree nodes: 
   # 1. Orange.CustomComponent or {Orange.CustomComponent, attrs} -> %Orange.CustomComponent{}
   defp normalize_tree_node(root) do
@@ -83,6 +136,14 @@ defmodule Orange.Runtime.RenderLoop do
 
   defp terminal_impl(), do: Application.get_env(:orange, :terminal, Terminal)
 
+  defp viewport_config(), do: Application.get_env(:orange, :viewport, :fullscreen)
+
+  defp mouse_capture?(), do: Application.get_env(:orange, :mouse_capture, false)
+
+  # In inline mode, the UI is rendered in the viewport lines only
+  defp viewport_size(terminal_size, :fullscreen), do: terminal_size
+  defp viewport_size({width, height}, {:inline, lines}), do: {width, min(height, lines)}
+
   defp event_manager_impl(),
     do: Application.get_env(:orange, :event_manager, Runtime.EventManager)
 
@@ -150,10 +211,16 @@ defmodule Orange.Runtime.RenderLoop do
     state =
       case event do
         %Terminal.ResizeEvent{width: w, height: h} ->
-          %{state | terminal_size: {w, h}}
+          %{state | terminal_size: viewport_size({w, h}, state.viewport)}
           |> render_tick(clean_buffer: true)
 
-        %Terminal.KeyEvent{} ->
+        # The screen content was lost while suspended, and the terminal might have been
+        # resized meanwhile
+        %Terminal.ResumeEvent{} ->
+          %{state | terminal_size: viewport_size(terminal_impl().terminal_size(), state.viewport)}
+          |> render_tick(clean_buffer: true)
+
+        _ ->
           if Runtime.ComponentRegistry.has_dirty_components?() do
             render_tick(state)
           else
@@ -164,6 +231,12 @@ defmodule Orange.Runtime.RenderLoop do
     {:noreply, state}
   end
 
+  @impl true
+  def handle_info({:event_reader_error, reason}, state) do
+    handle_terminal_error(reason)
+    {:noreply, state}
+  end
+
   @impl true
   def handle_info({:state_updated, version, time}, state) do
     latest_version = Runtime.ComponentRegistry.get_state_version()
@@ -204,18 +277,23 @@ defmodule Orange.Runtime.RenderLoop do
       Process.put({__MODULE__, :layout_tree_id_map}, layout_tree_id_map)
 
       Tracer.with_span "draw" do
-        if opts[:clean_buffer] do
-          terminal_impl().clear()
-          terminal_impl().draw(current_buffer, nil)
-        else
-          terminal_impl().draw(current_buffer, state.previous_buffer)
-        end
+        result =
+          if opts[:clean_buffer] do
+            terminal_impl().invalidate(state.frame_buffer)
+
+            with :ok <- terminal_impl().clear(),
+                 do: terminal_impl().present(state.frame_buffer, current_buffer)
+          else
+            terminal_impl().present(state.frame_buffer, current_buffer)
+          end
+
+        with {:error, reason} <- result, do: handle_terminal_error(reason)
       end
 
       after_mount(mounting_components)
       after_unmount(unmounting_components)
 
-      %{state | previous_tree: current_tree, previous_buffer: current_buffer}
+      %{state | previous_tree: current_tree}
     end
   end
 
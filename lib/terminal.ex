@@ -4,19 +4,89 @@ defmodule Orange.Terminal do
   # Provides API to interact with the terminal via Rust bindings.
 
   alias Orange.Renderer.{Cell, Buffer}
-  alias Orange.Terminal.KeyEvent
-
-  @callback draw(buffer :: Buffer.t()) :: :ok
-  @callback draw(buffer :: Buffer.t(), previous_buffer :: Buffer.t()) :: :ok
-  @callback poll_event() :: [KeyEvent.t()]
-  @callback enter_alternate_screen() :: :ok
-  @callback leave_alternate_screen() :: :ok
-  @callback enable_raw_mode() :: :ok
-  @callback disable_raw_mode() :: :ok
-  @callback show_cursor() :: :ok
-  @callback hide_cursor() :: :ok
-  @callback clear() :: :ok
+
+  @default_size {80, 24}
+
+  # Terminal operations return {:error, reason} instead of raising when the terminal
+  # can't be written to or read from, e.g. stdout is a closed pipe or not a TTY
+  @type result :: :ok | {:error, reason :: atom | String.t()}
+
+  @type clipboard_target :: :clipboard | :primary
+
+  @type rgb_color :: {:rgb, 0..255, 0..255, 0..255}
+
+  @type output_target ::
+          :stdout
+          | :stderr
+          | :tty
+          | {:fd, non_neg_integer()}
+          | {:virtual, Orange.Terminal.VirtualTerminal.t()}
+
+  @type keyboard_enhancement_flag ::
+          :disambiguate_escape_codes
+          | :report_event_types
+          | :report_alternate_keys
+          | :report_all_keys_as_escape_codes
+
+  @type cursor_shape ::
+          :default
+          | :blinking_block
+          | :steady_block
+          | :blinking_underline
+          | :steady_underline
+          | :blinking_bar
+          | :steady_bar
+
+  @callback draw(buffer :: Buffer.t()) :: result
+  @callback draw(buffer :: Buffer.t(), previous_buffer :: Buffer.t()) :: result
+  @callback new_frame_buffer() :: frame_buffer :: term()
+  @callback present(frame_buffer :: term(), buffer :: Buffer.t()) :: result
+  @callback invalidate(frame_buffer :: term()) :: :ok
+  @callback poll_event(timeout :: non_neg_integer()) ::
+              Orange.Component.event() | :timeout | {:error, term}
+  @callback drain_events(max :: pos_integer()) :: [Orange.Component.event()] | {:error, term}
+  @callback start_event_reader(receiver :: pid()) :: reader :: term()
+  @callback stop_event_reader(reader :: term()) :: :ok
+  @callback enter_alternate_screen() :: result
+  @callback leave_alternate_screen() :: result
+  @callback enable_raw_mode() :: result
+  @callback disable_raw_mode() :: result
+  @callback show_cursor() :: result
+  @callback hide_cursor() :: result
+  @callback move_cursor(x :: non_neg_integer(), y :: non_neg_integer()) :: result
+  @callback set_cursor_shape(shape :: cursor_shape) :: result
+  @callback save_cursor_position() :: result
+  @callback restore_cursor_position() :: result
+  @callback set_title(title :: String.t()) :: result
+  @callback push_title() :: result
+  @callback pop_title() :: result
+  @callback enable_mouse_capture() :: result
+  @callback disable_mouse_capture() :: result
+  @callback enable_bracketed_paste() :: result
+  @callback disable_bracketed_paste() :: result
+  @callback enable_focus_reporting() :: result
+  @callback disable_focus_reporting() :: result
+  @callback push_keyboard_enhancement_flags(flags :: [keyboard_enhancement_flag]) :: result
+  @callback pop_keyboard_enhancement_flags() :: result
+  @callback supports_keyboard_enhancement() :: boolean
+  @callback supports_synchronized_output() :: boolean
+  @callback set_synchronized_output(enabled :: boolean) :: :ok
+  @callback set_output(target :: output_target) :: result
+  @callback clear() :: result
+  @callback enter_inline_viewport(height :: pos_integer()) ::
+              {:ok, pos_integer()} | {:error, term}
+  @callback leave_inline_viewport() :: result
+  @callback restore_terminal() :: result
+  @callback start_recording(path :: Path.t(), opts :: keyword) :: result
+  @callback stop_recording() :: result
+  @callback suspend() :: result
+  @callback copy_to_clipboard(text :: String.t(), target :: clipboard_target) :: result
+  @callback request_clipboard(receiver :: pid(), target :: clipboard_target) :: :ok
   @callback terminal_size() :: {non_neg_integer(), non_neg_integer()}
+  @callback terminal_colors(timeout :: non_neg_integer()) :: %{
+              foreground: rgb_color | nil,
+              background: rgb_color | nil
+            }
 
   def draw(buffer, previous_buffer \\ nil)
 
@@ -24,23 +94,9 @@ defmodule Orange.Terminal do
   Draws the buffer to the terminal. If a previous buffer is provided, it will only draw the diff between the two buffers.
   """
   def draw(buffer, nil) do
-    cells =
-      buffer.rows
-      |> :array.to_list()
-      |> Enum.with_index()
-      |> Enum.flat_map(fn {row, row_index} ->
-        row
-        |> :array.to_list()
-        |> Enum.with_index()
-        |> Enum.map(fn
-          {:undefined, _} -> nil
-          {cell, col_index} -> {cell, col_index, row_index}
-        end)
-        |> Enum.reject(&is_nil/1)
-      end)
-
-    __MODULE__.Binding.draw(cells)
-    :ok
+    buffer
+    |> buffer_cells()
+    |> __MODULE__.Binding.draw()
   end
 
   def draw(buffer, previous_buffer) do
@@ -65,71 +121,374 @@ defmodule Orange.Terminal do
       end)
 
     __MODULE__.Binding.draw(diff_cells)
-    :ok
   end
 
   @doc """
-  Polls the terminal for events.
+  Returns the bytes `draw/1` would write to the terminal for the buffer, escape sequences
+  included, instead of writing them.
+
+  This is useful to test the terminal output, or to send frames somewhere else than the
+  terminal. The output depends on the terminal state the same way `draw/1` does, e.g. the
+  detected color depth and the inline viewport position.
+  """
+  def render_to_binary(buffer) do
+    buffer
+    |> buffer_cells()
+    |> __MODULE__.Binding.render_to_binary()
+  end
+
+  defp buffer_cells(buffer) do
+    buffer.rows
+    |> :array.to_list()
+    |> Enum.with_index()
+    |> Enum.flat_map(fn {row, row_index} ->
+      row
+      |> :array.to_list()
+      |> Enum.with_index()
+      |> Enum.map(fn
+        {:undefined, _} -> nil
+        {cell, col_index} -> {cell, col_index, row_index}
+      end)
+      |> Enum.reject(&is_nil/1)
+    end)
+  end
+
+  @doc """
+  Creates a frame buffer. The frame buffer keeps the last presented frame natively, so that
+  `present/2` only draws the cells which changed.
   """
-  defdelegate poll_event(), to: __MODULE__.Binding
+  defdelegate new_frame_buffer(), to: __MODULE__.Binding
 
   @doc """
-  Enter terminal alternate screen.
+  Draws the buffer to the terminal, diffing it against the last frame presented with the
+  same frame buffer.
   """
-  def enter_alternate_screen() do
-    __MODULE__.Binding.enter_alternate_screen()
-    :ok
+  def present(frame_buffer, buffer) do
+    rows =
+      buffer.rows
+      |> :array.to_list()
+      |> Enum.map(&:array.to_list/1)
+
+    __MODULE__.Binding.present(frame_buffer, rows)
   end
 
   @doc """
-  Leave terminal alternate screen.
+  Forces the next `present/2` to redraw every cell, e.g. after the terminal is cleared or resized.
+  """
+  defdelegate invalidate(frame_buffer), to: __MODULE__.Binding
+
+  @doc """
+  Polls the terminal for events. Waits at most `timeout` milliseconds and returns `:timeout`
+  if no event arrives.
   """
-  def leave_alternate_screen() do
-    __MODULE__.Binding.leave_alternate_screen()
-    :ok
+  def poll_event(timeout) do
+    case __MODULE__.Binding.poll_event(timeout) do
+      {:ok, event} -> event
+      other -> other
+    end
   end
 
   @doc """
-  Enable terminal raw mode.
+  Returns up to `max` events which are already buffered by the terminal, without blocking.
   """
-  def enable_raw_mode() do
-    __MODULE__.Binding.enable_raw_mode()
-    :ok
+  def drain_events(max) do
+    case __MODULE__.Binding.drain_events(max) do
+      {:ok, events} -> events
+      error -> error
+    end
   end
 
+  @doc """
+  Starts reading terminal events in a native thread. Events are sent to the receiver as
+  `{:event, event}` messages.
+
+  Returns a reader handle. The reader stops when `stop_event_reader/1` is called, when the
+  handle is garbage collected or when the receiver exits.
+  """
+  defdelegate start_event_reader(receiver), to: __MODULE__.Binding
+
+  @doc """
+  Stops a reader started by `start_event_reader/1`.
+  """
+  defdelegate stop_event_reader(reader), to: __MODULE__.Binding
+
+  @doc """
+  Enter terminal alternate screen.
+  """
+  defdelegate enter_alternate_screen(), to: __MODULE__.Binding
+
+  @doc """
+  Leave terminal alternate screen.
+  """
+  defdelegate leave_alternate_screen(), to: __MODULE__.Binding
+
+  @doc """
+  Enable terminal raw mode.
+  """
+  defdelegate enable_raw_mode(), to: __MODULE__.Binding
+
   @doc """
   Disable terminal raw mode.
   """
-  def disable_raw_mode() do
-    __MODULE__.Binding.disable_raw_mode()
-    :ok
-  end
+  defdelegate disable_raw_mode(), to: __MODULE__.Binding
 
   @doc """
   Show terminal cursor.
   """
-  def show_cursor() do
-    __MODULE__.Binding.show_cursor()
-    :ok
-  end
+  defdelegate show_cursor(), to: __MODULE__.Binding
 
   @doc """
   Hide terminal cursor.
   """
-  def hide_cursor() do
-    __MODULE__.Binding.hide_cursor()
-    :ok
+  defdelegate hide_cursor(), to: __MODULE__.Binding
+
+  @doc """
+  Move terminal cursor to the given column and row, starting from 0. In inline mode, the row is
+  relative to the viewport. The cursor is moved back there after each draw, so it can be used
+  as a caret, until the screen is redrawn with `invalidate/1`, switched or restored.
+  """
+  defdelegate move_cursor(x, y), to: __MODULE__.Binding
+
+  @doc """
+  Set terminal cursor shape. Supported shapes are `:blinking_block`, `:steady_block`,
+  `:blinking_underline`, `:steady_underline`, `:blinking_bar` and `:steady_bar`.
+  `:default` restores the shape configured by the user.
+  """
+  defdelegate set_cursor_shape(shape), to: __MODULE__.Binding
+
+  @doc """
+  Save terminal cursor position.
+  """
+  defdelegate save_cursor_position(), to: __MODULE__.Binding
+
+  @doc """
+  Restore terminal cursor position saved by `save_cursor_position/0`.
+  """
+  defdelegate restore_cursor_position(), to: __MODULE__.Binding
+
+  @doc """
+  Set terminal window title.
+  """
+  defdelegate set_title(title), to: __MODULE__.Binding
+
+  @doc """
+  Save terminal window title on the terminal title stack, so that it can be restored with `pop_title/0`.
+  """
+  defdelegate push_title(), to: __MODULE__.Binding
+
+  @doc """
+  Restore terminal window title saved by `push_title/0`.
+  """
+  defdelegate pop_title(), to: __MODULE__.Binding
+
+  @doc """
+  Enable mouse capture. The terminal will report mouse events.
+  """
+  defdelegate enable_mouse_capture(), to: __MODULE__.Binding
+
+  @doc """
+  Disable mouse capture.
+  """
+  defdelegate disable_mouse_capture(), to: __MODULE__.Binding
+
+  @doc """
+  Enable bracketed paste. Pasted text will be reported as a single paste event.
+  """
+  defdelegate enable_bracketed_paste(), to: __MODULE__.Binding
+
+  @doc """
+  Disable bracketed paste.
+  """
+  defdelegate disable_bracketed_paste(), to: __MODULE__.Binding
+
+  @doc """
+  Enable focus reporting. The terminal will report when its window gains or loses focus.
+  """
+  defdelegate enable_focus_reporting(), to: __MODULE__.Binding
+
+  @doc """
+  Disable focus reporting.
+  """
+  defdelegate disable_focus_reporting(), to: __MODULE__.Binding
+
+  @doc """
+  Push kitty keyboard protocol enhancement flags. Supported flags are:
+
+    * `:disambiguate_escape_codes` - report keys like Ctrl+I and Tab differently
+    * `:report_event_types` - report key repeat and release events
+    * `:report_alternate_keys` - report alternate key codes, e.g. shifted keys
+    * `:report_all_keys_as_escape_codes` - report all keys, including plain text keys, as escape codes
+  """
+  defdelegate push_keyboard_enhancement_flags(flags), to: __MODULE__.Binding
+
+  @doc """
+  Pop the keyboard enhancement flags pushed by `push_keyboard_enhancement_flags/1`.
+  """
+  defdelegate pop_keyboard_enhancement_flags(), to: __MODULE__.Binding
+
+  @doc """
+  Returns whether the terminal supports the kitty keyboard protocol.
+
+  Raw mode is enabled while waiting for the reply if needed. The reply is read as input, so
+  events must not be polled concurrently with `poll_event/1`.
+  """
+  def supports_keyboard_enhancement() do
+    case __MODULE__.Binding.supports_keyboard_enhancement() do
+      {:ok, supported} -> supported
+      # The terminal didn't reply to the probe
+      {:error, _reason} -> false
+    end
+  end
+
+  @doc """
+  Returns whether the terminal supports synchronized output (DEC mode 2026).
+
+  Raw mode is enabled while waiting for the reply if needed. The reply is read as input, so
+  events must not be polled concurrently with `poll_event/1`.
+  """
+  def supports_synchronized_output() do
+    case __MODULE__.Binding.supports_synchronized_output() do
+      {:ok, supported} -> supported
+      {:error, _reason} -> false
+    end
+  end
+
+  @doc """
+  Turn synchronized output on or off. When on, each draw is wrapped in a synchronized
+  update, so that the terminal displays the whole frame at once.
+  """
+  defdelegate set_synchronized_output(enabled), to: __MODULE__.Binding
+
+  @doc """
+  Copy the text to the terminal clipboard with OSC 52. The target is either `:clipboard` or
+  `:primary` for the primary selection. Since the terminal sets the clipboard, this also works
+  over SSH. Terminals which don't support OSC 52 ignore it.
+  """
+  defdelegate copy_to_clipboard(text, target), to: __MODULE__.Binding
+
+  @doc """
+  Ask the terminal for the clipboard content with OSC 52. The content is sent to the receiver
+  as an `{:event, %Orange.Terminal.ClipboardEvent{}}` message.
+
+  Most terminals don't allow reading the clipboard by default. Nothing is sent if the terminal
+  doesn't reply. Terminals which ask the user first are not supported: their reply comes after
+  the request is over, and is read as key presses.
+  """
+  defdelegate request_clipboard(receiver, target), to: __MODULE__.Binding
+
+  @doc """
+  Select where the terminal output is written. Supported targets are:
+
+    * `:stdout` (default)
+    * `:stderr`
+    * `:tty` - the controlling terminal, i.e. `/dev/tty`
+    * `{:fd, fd}` - a file descriptor, which is duplicated so the caller keeps ownership of it
+    * `{:virtual, terminal}` - an `Orange.Terminal.VirtualTerminal`, e.g. for end-to-end tests
+
+  Events are read from stdin if it's a TTY, otherwise from the controlling terminal.
+
+  A file descriptor only receives the output, since it might not be the terminal events are
+  read from. While it's the output, enabling raw mode, polling events and entering an inline
+  viewport return `{:error, :unsupported}`, `terminal_size/0` returns a default size and the
+  terminal is assumed to support nothing it is queried for. Use `:tty` to draw on the
+  controlling terminal.
+  """
+  defdelegate set_output(target), to: __MODULE__.Binding
+
+  @doc """
+  Clear all terminal. In inline mode, only the viewport is cleared.
+  """
+  defdelegate clear(), to: __MODULE__.Binding
+
+  @doc """
+  Reserve `height` lines under the cursor and draw the next frames in them, instead of the
+  whole screen. The screen is scrolled up if there are not enough lines below the cursor.
+
+  Returns `{:ok, height}`, where the height is capped to the terminal height. Events must not
+  be polled concurrently, since the cursor position is read from the terminal.
+  """
+  defdelegate enter_inline_viewport(height), to: __MODULE__.Binding
+
+  @doc """
+  Move the cursor below the inline viewport. The last frame stays on the screen.
+  """
+  defdelegate leave_inline_viewport(), to: __MODULE__.Binding
+
+  @doc """
+  Undo every terminal mode enabled through this module: raw mode, alternate screen, inline
+  viewport, hidden cursor, cursor shape, input modes, keyboard enhancement flags and pushed
+  titles.
+
+  The same is done natively when the NIF library is unloaded, the VM exits or the VM is
+  killed by a fatal signal, e.g. `SIGHUP` or `SIGSEGV`, so the shell is usable again even if
+  the runtime doesn't stop cleanly. `SIGTERM` stops the VM gracefully, and the runtime
+  restores the terminal while stopping.
+  """
+  defdelegate restore_terminal(), to: __MODULE__.Binding
+
+  @doc """
+  Record the terminal session to an
+  [asciicast v2](https://docs.asciinema.org/manual/asciicast/v2/) file at `path`, replacing
+  it. Every byte written to the terminal and every resize is timestamped and appended to the
+  file, until `stop_recording/0` is called. Recordings can be replayed with `asciinema play`,
+  or converted to a GIF, e.g. with `agg`.
+
+  The file is written as events happen, so it's usable even if the VM crashes.
+
+  ## Options
+
+    * `:input` - also record the input, as the bytes a terminal sends for each key press or
+      paste. Defaults to `false`, since the input might contain secrets.
+  """
+  def start_recording(path, opts \\ []),
+    do: __MODULE__.Binding.start_recording(path, Keyword.get(opts, :input, false))
+
+  @doc """
+  Stop the active recording, if any.
+  """
+  defdelegate stop_recording(), to: __MODULE__.Binding
+
+  @doc """
+  Suspend the process like Ctrl+Z does in a shell. The terminal is restored first, then the
+  process is stopped with `SIGTSTP`.
+
+  Returns once the process is continued, e.g. with `fg`, after the terminal modes are enabled
+  again. The next event is then an `Orange.Terminal.ResumeEvent`. Only supported on Unix.
+  """
+  defdelegate suspend(), to: __MODULE__.Binding
+
+  @doc """
+  Returns the terminal size as `{width, height}`. Falls back to #{inspect(@default_size)} if the
+  size can't be queried, e.g. the output is not a TTY. With a virtual terminal output, this is
+  the size of the virtual terminal.
+  """
+  def terminal_size() do
+    case __MODULE__.Binding.terminal_size() do
+      {:ok, size} -> size
+      {:error, _reason} -> @default_size
+    end
   end
 
   @doc """
-  Clear all terminal.
+  Returns the terminal default foreground and background colors, as `{:rgb, r, g, b}`.
+  Colors which the terminal doesn't report within `timeout` milliseconds are `nil`.
+
+  The terminal reply is read as input, so events must not be polled concurrently.
   """
-  def clear() do
-    __MODULE__.Binding.clear()
-    :ok
+  def terminal_colors(timeout) do
+    case __MODULE__.Binding.query_terminal_colors(timeout) do
+      {:ok, colors} -> colors
+      {:error, _reason} -> %{foreground: nil, background: nil}
+    end
   end
 
-  defdelegate terminal_size(), to: __MODULE__.Binding
+  @doc """
+  Returns the color depth supported by the terminal, detected from `COLORTERM` and `TERM`. On
+  Windows, the console supports true color when `TERM` is not set.
+
+  Colors which can't be displayed with this depth are downsampled when drawing.
+  """
+  defdelegate color_depth(), to: __MODULE__.Binding
 
   defmodule Binding do
     @moduledoc false
@@ -154,14 +513,55 @@ defmodule Orange.Terminal do
       nif_versions: ["2.15", "2.16"]
 
     def draw(_buffer), do: :erlang.nif_error(:nif_not_loaded)
+    def render_to_binary(_cells), do: :erlang.nif_error(:nif_not_loaded)
+    def new_frame_buffer(), do: :erlang.nif_error(:nif_not_loaded)
+    def present(_frame_buffer, _rows), do: :erlang.nif_error(:nif_not_loaded)
+    def invalidate(_frame_buffer), do: :erlang.nif_error(:nif_not_loaded)
     def enter_alternate_screen(), do: :erlang.nif_error(:nif_not_loaded)
     def leave_alternate_screen(), do: :erlang.nif_error(:nif_not_loaded)
     def enable_raw_mode(), do: :erlang.nif_error(:nif_not_loaded)
     def disable_raw_mode(), do: :erlang.nif_error(:nif_not_loaded)
     def show_cursor(), do: :erlang.nif_error(:nif_not_loaded)
     def hide_cursor(), do: :erlang.nif_error(:nif_not_loaded)
+    def move_cursor(_x, _y), do: :erlang.nif_error(:nif_not_loaded)
+    def set_cursor_shape(_shape), do: :erlang.nif_error(:nif_not_loaded)
+    def save_cursor_position(), do: :erlang.nif_error(:nif_not_loaded)
+    def restore_cursor_position(), do: :erlang.nif_error(:nif_not_loaded)
+    def set_title(_title), do: :erlang.nif_error(:nif_not_loaded)
+    def push_title(), do: :erlang.nif_error(:nif_not_loaded)
+    def pop_title(), do: :erlang.nif_error(:nif_not_loaded)
+    def enable_mouse_capture(), do: :erlang.nif_error(:nif_not_loaded)
+    def disable_mouse_capture(), do: :erlang.nif_error(:nif_not_loaded)
+    def enable_bracketed_paste(), do: :erlang.nif_error(:nif_not_loaded)
+    def disable_bracketed_paste(), do: :erlang.nif_error(:nif_not_loaded)
+    def enable_focus_reporting(), do: :erlang.nif_error(:nif_not_loaded)
+    def disable_focus_reporting(), do: :erlang.nif_error(:nif_not_loaded)
+    def push_keyboard_enhancement_flags(_flags), do: :erlang.nif_error(:nif_not_loaded)
+    def pop_keyboard_enhancement_flags(), do: :erlang.nif_error(:nif_not_loaded)
+    def supports_keyboard_enhancement(), do: :erlang.nif_error(:nif_not_loaded)
+    def supports_synchronized_output(), do: :erlang.nif_error(:nif_not_loaded)
+    def set_synchronized_output(_enabled), do: :erlang.nif_error(:nif_not_loaded)
+    def set_output(_target), do: :erlang.nif_error(:nif_not_loaded)
+    def copy_to_clipboard(_text, _target), do: :erlang.nif_error(:nif_not_loaded)
+    def request_clipboard(_receiver, _target), do: :erlang.nif_error(:nif_not_loaded)
     def clear(), do: :erlang.nif_error(:nif_not_loaded)
-    def poll_event(), do: :erlang.nif_error(:nif_not_loaded)
+    def enter_inline_viewport(_height), do: :erlang.nif_error(:nif_not_loaded)
+    def leave_inline_viewport(), do: :erlang.nif_error(:nif_not_loaded)
+    def restore_terminal(), do: :erlang.nif_error(:nif_not_loaded)
+    def start_recording(_path, _input), do: :erlang.nif_error(:nif_not_loaded)
+    def stop_recording(), do: :erlang.nif_error(:nif_not_loaded)
+    def new_virtual_terminal(_width, _height), do: :erlang.nif_error(:nif_not_loaded)
+    def virtual_terminal_write(_terminal, _bytes), do: :erlang.nif_error(:nif_not_loaded)
+    def virtual_terminal_resize(_terminal, _width, _height),
+      do: :erlang.nif_error(:nif_not_loaded)
+    def virtual_terminal_screen(_terminal), do: :erlang.nif_error(:nif_not_loaded)
+    def suspend(), do: :erlang.nif_error(:nif_not_loaded)
+    def poll_event(_timeout), do: :erlang.nif_error(:nif_not_loaded)
+    def drain_events(_max), do: :erlang.nif_error(:nif_not_loaded)
+    def start_event_reader(_receiver), do: :erlang.nif_error(:nif_not_loaded)
+    def stop_event_reader(_reader), do: :erlang.nif_error(:nif_not_loaded)
     def terminal_size(), do: :erlang.nif_error(:nif_not_loaded)
+    def color_depth(), do: :erlang.nif_error(:nif_not_loaded)
+    def query_terminal_colors(_timeout), do: :erlang.nif_error(:nif_not_loaded)
   end
 end
@@ -3,6 +3,10 @@ defmodule Orange.Terminal.KeyEvent do
   Terminal keyboard events.
 
   See `key_code/0` below for supported keys.
+
+  The `kind` and `state` fields are only populated by terminals which support the kitty keyboard
+  protocol, after the corresponding enhancement flags are pushed. Otherwise, every event is a
+  `:press` with an empty `state`.
   """
 
   @type key_code ::
@@ -43,14 +47,21 @@ defmodule Orange.Terminal.KeyEvent do
           | :keypad_begin
           | {:char, String.t()}
 
-  @type key_modifier :: :shift | :ctrl | :alt | :super | :hyper
+  @type key_modifier :: :shift | :control | :alt | :super | :hyper
+
+  @type kind :: :press | :repeat | :release
+
+  # Caps lock and num lock are reported with the same flag, so they can't be told apart
+  @type key_state :: :keypad | :caps_or_num_lock
 
   @type t :: %__MODULE__{
           code: key_code,
-          modifiers: [key_modifier]
+          modifiers: [key_modifier],
+          kind: kind,
+          state: [key_state]
         }
 
-  defstruct [:code, :modifiers]
+  defstruct [:code, :modifiers, kind: :press, state: []]
 end
 
 defmodule Orange.Terminal.ResizeEvent do
@@ -65,3 +76,92 @@ defmodule Orange.Terminal.ResizeEvent do
 
   defstruct [:width, :height]
 end
+
+defmodule Orange.Terminal.PasteEvent do
+  @moduledoc """
+  Terminal paste event.
+
+  Pasted text is delivered as a single event instead of one key event per character. Requires
+  bracketed paste to be enabled, which the runtime does on startup.
+  """
+
+  @type t :: %__MODULE__{
+          content: String.t()
+        }
+
+  defstruct [:content]
+end
+
+defmodule Orange.Terminal.FocusEvent do
+  @moduledoc """
+  Terminal focus event.
+
+  Emitted when the terminal window gains or loses focus. Requires focus reporting to be enabled.
+  """
+
+  @type t :: %__MODULE__{
+          focused: boolean
+        }
+
+  defstruct [:focused]
+end
+
+defmodule Orange.Terminal.ResumeEvent do
+  @moduledoc """
+  Terminal resume event.
+
+  Emitted when the process is continued after `Orange.Terminal.suspend/0`. The screen content
+  is lost while suspended, so the whole screen is redrawn.
+  """
+
+  @type t :: %__MODULE__{}
+
+  defstruct []
+end
+
+defmodule Orange.Terminal.ClipboardEvent do
+  @moduledoc """
+  Terminal clipboard event.
+
+  Emitted when the terminal replies to `Orange.request_clipboard/1` with the content of the
+  clipboard or of the primary selection.
+  """
+
+  @type t :: %__MODULE__{
+          target: :clipboard | :primary,
+          content: String.t()
+        }
+
+  defstruct [:target, :content]
+end
+
+defmodule Orange.Terminal.MouseEvent do
+  @moduledoc """
+  Terminal mouse event.
+
+  Mouse events are only reported after mouse capture is enabled. The `button` field is set for
+  `:down`, `:up` and `:drag` events, and is `nil` otherwise. `column` and `row` are zero-based.
+  """
+
+  @type kind ::
+          :down
+          | :up
+          | :drag
+          | :moved
+          | :scroll_up
+          | :scroll_down
+          | :scroll_left
+          | :scroll_right
+
+  @type button :: :left | :right | :middle
+
+  @type t :: %__MODULE__{
+          kind: kind,
+          button: button | nil,
+          column: non_neg_integer,
+          row: non_neg_integer,
+          modifiers: [Orange.Terminal.KeyEvent.key_modifier()]
+        }
+
+  defstruct [:kind, :button, :column, :row, modifiers: []]
+end
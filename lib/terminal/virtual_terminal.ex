@@ -0,0 +1,129 @@
+defmodule Orange.Terminal.VirtualTerminal do
+  @moduledoc """
+  A headless terminal emulator.
+
+  A virtual terminal parses the escape sequences written by `Orange.Terminal` and keeps what a
+  real terminal would display: a grid of styled cells, the cursor position and the terminal
+  modes. It's useful to test the actual terminal output without a TTY.
+
+  Bytes can be fed to it directly, e.g. the output of `Orange.Terminal.render_to_binary/1`, or it
+  can replace the terminal output with `Orange.Terminal.set_output({:virtual, terminal})`. In the
+  latter case, raw mode is enabled on the virtual terminal instead of the real one. Terminal
+  queries are not answered.
+
+  ## Example
+
+      terminal = VirtualTerminal.new({10, 2})
+      VirtualTerminal.write(terminal, Orange.Terminal.render_to_binary(buffer))
+
+      screen = VirtualTerminal.screen(terminal)
+      VirtualTerminal.Screen.to_string(screen)
+  """
+
+  alias Orange.Terminal.Binding
+
+  defmodule Cell do
+    @moduledoc """
+    A cell of a virtual terminal screen.
+
+    Colors use the same format as the colors given to the terminal: named colors are atoms, e.g.
+    `:red`. The second column of a wide character is a cell with an empty `character`.
+    """
+
+    @type color :: atom | {:ansi256, 0..255} | Orange.Terminal.rgb_color()
+
+    @type t :: %__MODULE__{
+            character: String.t(),
+            foreground: color | nil,
+            background: color | nil,
+            underline_color: color | nil,
+            modifiers: [atom],
+            hyperlink: String.t() | nil
+          }
+
+    defstruct [
+      :foreground,
+      :background,
+      :underline_color,
+      :hyperlink,
+      modifiers: [],
+      character: " "
+    ]
+  end
+
+  defmodule Screen do
+    @moduledoc """
+    What a virtual terminal displays, and its modes.
+    """
+
+    @type t :: %__MODULE__{
+            width: non_neg_integer,
+            height: non_neg_integer,
+            rows: [[Cell.t()]],
+            cursor: {non_neg_integer, non_neg_integer},
+            cursor_visible: boolean,
+            cursor_shape: Orange.Terminal.cursor_shape(),
+            alternate_screen: boolean,
+            raw_mode: boolean,
+            bracketed_paste: boolean,
+            mouse_capture: boolean,
+            focus_reporting: boolean,
+            synchronized_output: boolean,
+            keyboard_enhancement_flags: [atom],
+            title: String.t()
+          }
+
+    defstruct [
+      :width,
+      :height,
+      :cursor,
+      :cursor_shape,
+      :title,
+      rows: [],
+      cursor_visible: true,
+      alternate_screen: false,
+      raw_mode: false,
+      bracketed_paste: false,
+      mouse_capture: false,
+      focus_reporting: false,
+      synchronized_output: false,
+      keyboard_enhancement_flags: []
+    ]
+
+    @doc """
+    Returns the displayed text, one line per row.
+    """
+    def to_string(%__MODULE__{rows: rows}) do
+      Enum.map_join(rows, "\n", fn row -> Enum.map_join(row, & &1.character) end)
+    end
+
+    @doc """
+    Returns the cell at the given coordinates.
+    """
+    def get_cell(%__MODULE__{rows: rows}, x, y), do: rows |> Enum.at(y) |> Enum.at(x)
+  end
+
+  @type t :: reference()
+
+  @doc """
+  Creates a virtual terminal of the given size. The screen is blank and the cursor is at the
+  top left corner.
+  """
+  def new({width, height}), do: Binding.new_virtual_terminal(width, height)
+
+  @doc """
+  Feeds bytes to the virtual terminal, as if they were written to its output.
+  """
+  defdelegate write(terminal, bytes), to: Binding, as: :virtual_terminal_write
+
+  @doc """
+  Resizes the virtual terminal. Cells outside of the new size are dropped.
+  """
+  def resize(terminal, {width, height}),
+    do: Binding.virtual_terminal_resize(terminal, width, height)
+
+  @doc """
+  Returns what the virtual terminal displays, and its modes.
+  """
+  defdelegate screen(terminal), to: Binding, as: :virtual_terminal_screen
+end
@@ -17,12 +17,19 @@ defmodule Orange.Test do
     * `:events` - a list of mock events from the terminal. These events will be consumed by Orange in the same order as they are specified. This option is optional. Supported event types:
 
       * `Orange.Terminal.KeyEvent` - simulates a key event
+      * `Orange.Terminal.MouseEvent` - simulates a mouse event
+      * `Orange.Terminal.PasteEvent` - simulates a paste event
+      * `Orange.Terminal.FocusEvent` - simulates the terminal window gaining or losing focus
+      * `Orange.Terminal.ClipboardEvent` - simulates the terminal reply to a clipboard request
       * `Orange.Terminal.ResizeEvent` - simulates a terminal resize event
+      * `Orange.Terminal.ResumeEvent` - simulates resuming after a suspend
       * `{:wait, ms}` - wait for `ms` milliseconds
       * `{:function, fun}` - execute the given function
 
     * `:stop_after_last_event` - whether to stop the runtime after the last event is consumed. This option is optional. Defaults to `true`.
 
+    * `:virtual_terminal` - whether to also draw the frames to an `Orange.Terminal.VirtualTerminal`. The snapshots then contain the screen a real terminal would display, which can be checked with `Orange.Test.Assertions.assert_screen/2`. The terminal output is the virtual terminal until the render is done, and `:terminal_size` is required. This option is optional. Defaults to `false`.
+
   ## Examples
 
       [snapshot1, snapshot2, snapshot3 | _] = Test.render({MyComponent, open: true},
@@ -48,9 +55,13 @@ defmodule Orange.Test do
       {:DOWN, ^ref, :process, _pid, _reason} -> :ok
     end
 
-    for buffer <- __MODULE__.MockTerminal.get_captured_buffers() do
-      %__MODULE__.Snapshot{buffer: buffer}
-    end
+    __MODULE__.MockTerminal.teardown()
+
+    Enum.zip_with(
+      __MODULE__.MockTerminal.get_captured_buffers(),
+      __MODULE__.MockTerminal.get_captured_screens(),
+      fn buffer, screen -> %__MODULE__.Snapshot{buffer: buffer, screen: screen} end
+    )
   end
 
   @doc """
@@ -80,8 +91,12 @@ defmodule Orange.Test do
       {:DOWN, ^ref, :process, _pid, _reason} -> :ok
     end
 
+    virtual_terminal = __MODULE__.MockTerminal.virtual_terminal()
+    screen = if virtual_terminal, do: Orange.Terminal.VirtualTerminal.screen(virtual_terminal)
+    __MODULE__.MockTerminal.teardown()
+
     buffer = hd(__MODULE__.MockTerminal.get_drawn_buffers())
-    %__MODULE__.Snapshot{buffer: buffer}
+    %__MODULE__.Snapshot{buffer: buffer, screen: screen}
   end
 
   @doc """
@@ -116,6 +131,8 @@ defmodule Orange.Test do
         ExUnit.Assertions.flunk("Expected the render loop process to exit, but it didn't")
     end
 
+    __MODULE__.MockTerminal.teardown()
+
     receive do
       {:EXIT, _, {error, _}} ->
         error
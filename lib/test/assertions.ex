@@ -9,6 +9,7 @@ defmodule Orange.Test.Assertions do
   require ExUnit.Assertions
 
   alias Orange.Renderer.Buffer
+  alias Orange.Terminal.VirtualTerminal
 
   @doc """
   Asserts that the string representation of the buffer matches the expected content.
@@ -30,6 +31,28 @@ defmodule Orange.Test.Assertions do
     ExUnit.Assertions.assert(content == expected)
   end
 
+  @doc """
+  Asserts that the virtual terminal screen of the snapshot displays the expected content. Unlike
+  `assert_content/2`, this checks the escape sequences written by the terminal binding, as a real
+  terminal would display them. Requires rendering with the `virtual_terminal: true` option.
+
+  Empty cells are whitespaces, and lines are as wide as the terminal.
+
+  ## Example
+
+      assert_screen(snapshot, \"\"\"
+      Hello
+      World\\
+      \"\"\")
+  """
+  def assert_screen(%Orange.Test.Snapshot{screen: nil}, _expected),
+    do: ExUnit.Assertions.flunk("The snapshot has no screen, render with virtual_terminal: true")
+
+  def assert_screen(%Orange.Test.Snapshot{screen: screen}, expected) do
+    content = VirtualTerminal.Screen.to_string(screen)
+    ExUnit.Assertions.assert(content == expected)
+  end
+
   @doc """
   Asserts that cells have the specified foreground color.
 
@@ -4,18 +4,33 @@ defmodule Orange.Test.MockTerminal do
   # Mock terminal for testing
   # Users can setup the mock terminal with mock events and terminal size
   # Draw function will store the rendered buffers and can be accessed with `get_drawn_buffers/0`
+  # With the `virtual_terminal` option, the buffers are also drawn by the native binding, with
+  # a virtual terminal as the output, so that snapshots contain what a real terminal would
+  # display
 
   @behaviour Orange.Terminal
 
+  alias Orange.Terminal.VirtualTerminal
+
   def setup(opts) do
     # We store in ETS table so that we can access data from any processes
     :ets.new(__MODULE__.Storage, [:set, :public, :named_table])
     :ets.insert(__MODULE__.Storage, {:buffers, []})
     :ets.insert(__MODULE__.Storage, {:captured_buffers, []})
+    :ets.insert(__MODULE__.Storage, {:captured_screens, []})
 
     terminal_size = Keyword.get(opts, :terminal_size)
     if terminal_size, do: :ets.insert(__MODULE__.Storage, {:terminal_size, terminal_size})
 
+    if Keyword.get(opts, :virtual_terminal, false) do
+      unless terminal_size,
+        do: raise(ArgumentError, "the :virtual_terminal option requires a :terminal_size")
+
+      virtual_terminal = VirtualTerminal.new(terminal_size)
+      :ok = Orange.Terminal.set_output({:virtual, virtual_terminal})
+      :ets.insert(__MODULE__.Storage, {:virtual_terminal, virtual_terminal})
+    end
+
     events = Keyword.get(opts, :events)
     stop_after_last_event = Keyword.get(opts, :stop_after_last_event, true)
 
@@ -27,6 +42,12 @@ defmodule Orange.Test.MockTerminal do
         )
   end
 
+  # Draws to stdout again after a render with a virtual terminal
+  def teardown() do
+    if virtual_terminal(), do: Orange.Terminal.set_output(:stdout)
+    :ok
+  end
+
   def get_drawn_buffers() do
     [{_, buffers}] = :ets.lookup(__MODULE__.Storage, :buffers)
     buffers
@@ -37,21 +58,81 @@ defmodule Orange.Test.MockTerminal do
     buffers
   end
 
+  # The virtual terminal screens at the time of each snapshot, or nils without a virtual terminal
+  def get_captured_screens() do
+    [{_, screens}] = :ets.lookup(__MODULE__.Storage, :captured_screens)
+    screens
+  end
+
+  # The virtual terminal the frames are drawn to, if enabled
+  def virtual_terminal() do
+    case :ets.lookup(__MODULE__.Storage, :virtual_terminal) do
+      [{_, virtual_terminal}] -> virtual_terminal
+      _ -> nil
+    end
+  end
+
   @impl true
-  def draw(buffer, _previous_buffer \\ nil) do
+  def draw(buffer, previous_buffer \\ nil) do
+    record_buffer(buffer)
+    if virtual_terminal(), do: Orange.Terminal.draw(buffer, previous_buffer), else: :ok
+  end
+
+  defp record_buffer(buffer) do
     [{_, buffers}] = :ets.lookup(__MODULE__.Storage, :buffers)
     :ets.insert(__MODULE__.Storage, {:buffers, buffers ++ [buffer]})
   end
 
   @impl true
-  def poll_event() do
+  def new_frame_buffer() do
+    if virtual_terminal(), do: Orange.Terminal.new_frame_buffer(), else: make_ref()
+  end
+
+  # The native frame buffer blanks the cells which became empty, the same as with a real
+  # terminal
+  @impl true
+  def present(frame_buffer, buffer) do
+    record_buffer(buffer)
+    if virtual_terminal(), do: Orange.Terminal.present(frame_buffer, buffer), else: :ok
+  end
+
+  @impl true
+  def invalidate(frame_buffer) do
+    if virtual_terminal(), do: Orange.Terminal.invalidate(frame_buffer), else: :ok
+  end
+
+  @impl true
+  def poll_event(timeout) do
     case :ets.lookup(__MODULE__.Storage, :events) do
       [{_, events, counter, stop_after_last_event}] ->
         next_event(events, counter, stop_after_last_event)
 
       _ ->
-        Process.sleep(:infinity)
+        Process.sleep(timeout)
+        :timeout
+    end
+  end
+
+  @impl true
+  def drain_events(_max), do: []
+
+  @impl true
+  def start_event_reader(receiver), do: spawn_link(fn -> forward_events(receiver) end)
+
+  @impl true
+  def stop_event_reader(reader) do
+    Process.unlink(reader)
+    Process.exit(reader, :kill)
+    :ok
+  end
+
+  defp forward_events(receiver) do
+    case poll_event(100) do
+      :timeout -> :ok
+      event -> send(receiver, {:event, event})
     end
+
+    forward_events(receiver)
   end
 
   defp next_event(events, counter, stop_after_last_event) do
@@ -81,6 +162,12 @@ defmodule Orange.Test.MockTerminal do
         if stop_after_last_event, do: Orange.stop()
         Process.sleep(:infinity)
 
+      %Orange.Terminal.ResizeEvent{width: width, height: height} ->
+        if virtual_terminal = virtual_terminal(),
+          do: VirtualTerminal.resize(virtual_terminal, {width, height})
+
+        event
+
       _ ->
         event
     end
@@ -94,6 +181,11 @@ defmodule Orange.Test.MockTerminal do
       __MODULE__.Storage,
       {:captured_buffers, captured_buffers ++ [List.last(drawn_buffers)]}
     )
+
+    virtual_terminal = virtual_terminal()
+    screen = if virtual_terminal, do: VirtualTerminal.screen(virtual_terminal)
+    [{_, captured_screens}] = :ets.lookup(__MODULE__.Storage, :captured_screens)
+    :ets.insert(__MODULE__.Storage, {:captured_screens, captured_screens ++ [screen]})
   end
 
   @impl true
@@ -115,7 +207,101 @@ defmodule Orange.Test.MockTerminal do
   def hide_cursor(), do: :ok
 
   @impl true
-  def clear(), do: :ok
+  def move_cursor(_x, _y), do: :ok
+
+  @impl true
+  def set_cursor_shape(_shape), do: :ok
+
+  @impl true
+  def save_cursor_position(), do: :ok
+
+  @impl true
+  def restore_cursor_position(), do: :ok
+
+  @impl true
+  def set_title(_title), do: :ok
+
+  @impl true
+  def push_title(), do: :ok
+
+  @impl true
+  def pop_title(), do: :ok
+
+  @impl true
+  def enable_mouse_capture() do
+    if virtual_terminal(), do: Orange.Terminal.enable_mouse_capture(), else: :ok
+  end
+
+  @impl true
+  def disable_mouse_capture() do
+    if virtual_terminal(), do: Orange.Terminal.disable_mouse_capture(), else: :ok
+  end
+
+  @impl true
+  def enable_bracketed_paste(), do: :ok
+
+  @impl true
+  def disable_bracketed_paste(), do: :ok
+
+  @impl true
+  def enable_focus_reporting() do
+    if virtual_terminal(), do: Orange.Terminal.enable_focus_reporting(), else: :ok
+  end
+
+  @impl true
+  def disable_focus_reporting() do
+    if virtual_terminal(), do: Orange.Terminal.disable_focus_reporting(), else: :ok
+  end
+
+  @impl true
+  def push_keyboard_enhancement_flags(_flags), do: :ok
+
+  @impl true
+  def pop_keyboard_enhancement_flags(), do: :ok
+
+  @impl true
+  def supports_keyboard_enhancement(), do: false
+
+  @impl true
+  def supports_synchronized_output(), do: false
+
+  @impl true
+  def set_synchronized_output(_enabled), do: :ok
+
+  @impl true
+  def set_output(_target), do: :ok
+
+  @impl true
+  def copy_to_clipboard(_text, _target), do: :ok
+
+  @impl true
+  def request_clipboard(_receiver, _target), do: :ok
+
+  @impl true
+  def clear() do
+    if virtual_terminal(), do: Orange.Terminal.clear(), else: :ok
+  end
+
+  @impl true
+  def enter_inline_viewport(height), do: {:ok, height}
+
+  @impl true
+  def leave_inline_viewport(), do: :ok
+
+  @impl true
+  def restore_terminal(), do: :ok
+
+  @impl true
+  def start_recording(_path, _opts), do: :ok
+
+  @impl true
+  def stop_recording(), do: :ok
+
+  @impl true
+  def suspend(), do: :ok
+
+  @impl true
+  def terminal_colors(_timeout), do: %{foreground: nil, background: nil}
 
   @impl true
   def terminal_size() do
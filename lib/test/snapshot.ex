@@ -1,11 +1,17 @@
 defmodule Orange.Test.Snapshot do
   @moduledoc """
   A snapshot of a terminal buffer captured during a test.
+
+  When the test renders to a virtual terminal, the snapshot also contains the
+  `Orange.Terminal.VirtualTerminal.Screen` displayed at the time.
   """
 
-  @type t :: %__MODULE__{buffer: Orange.Renderer.Buffer}
+  @type t :: %__MODULE__{
+          buffer: Orange.Renderer.Buffer,
+          screen: Orange.Terminal.VirtualTerminal.Screen.t() | nil
+        }
 
-  defstruct [:buffer]
+  defstruct [:buffer, :screen]
 
   @doc """
   Returns the content of the snapshot as a string.
@@ -47,12 +47,20 @@ defmodule Orange.MixProject do
         ],
         Events: [
           Orange.Terminal.KeyEvent,
-          Orange.Terminal.ResizeEvent
+          Orange.Terminal.MouseEvent,
+          Orange.Terminal.PasteEvent,
+          Orange.Terminal.FocusEvent,
+          Orange.Terminal.ClipboardEvent,
+          Orange.Terminal.ResizeEvent,
+          Orange.Terminal.ResumeEvent
         ],
         Test: [
           Orange.Test,
           Orange.Test.Assertions,
-          Orange.Test.Snapshot
+          Orange.Test.Snapshot,
+          Orange.Terminal.VirtualTerminal,
+          Orange.Terminal.VirtualTerminal.Screen,
+          Orange.Terminal.VirtualTerminal.Cell
         ]
       ]
     ]
@@ -0,0 +1,101 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crossterm::style::Print;
+use rustler::NifUnitEnum;
+
+use crate::query;
+use crate::render::{flush, queue_command};
+
+#[derive(Debug, Clone, Copy, NifUnitEnum)]
+pub enum ClipboardTarget {
+    Clipboard,
+    Primary,
+}
+
+impl ClipboardTarget {
+    // The selection parameter of OSC 52
+    fn selection(self) -> &'static str {
+        match self {
+            ClipboardTarget::Clipboard => "c",
+            ClipboardTarget::Primary => "p",
+        }
+    }
+}
+
+// The read stops at the device attributes reply, so this only bounds the wait for terminals
+// which don't answer it
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Sets the clipboard of the terminal with OSC 52. Since the terminal sets it, this works
+// over SSH too. Terminals which don't support OSC 52 ignore it.
+pub fn copy(writer: &mut impl Write, text: &str, target: ClipboardTarget) -> io::Result<()> {
+    let sequence = format!(
+        "\x1b]52;{};{}\x1b\\",
+        target.selection(),
+        STANDARD.encode(text)
+    );
+
+    queue_command(writer, Print(sequence))?;
+    flush(writer)
+}
+
+// Asks the terminal for the clipboard content with OSC 52. Returns None if the terminal
+// doesn't reply, e.g. it doesn't support reading the clipboard or the user denied it.
+//
+// Terminals which support reading the clipboard reply before the device attributes, unless
+// they ask the user first. Then the device attributes reply comes first, and we can't tell
+// it apart from a terminal which doesn't support reading, so the read stops there. The late
+// reply is lost, and read as key presses by the event reader.
+pub fn read(target: ClipboardTarget) -> io::Result<Option<String>> {
+    let request = format!(
+        "\x1b]52;{};?\x1b\\{}",
+        target.selection(),
+        query::DEVICE_ATTRIBUTES_REQUEST
+    );
+
+    let reply = query::query(&request, READ_TIMEOUT, |reply| {
+        parse_reply(reply).is_some() || query::has_device_attributes(reply)
+    })?;
+
+    Ok(parse_reply(&reply))
+}
+
+// The reply is OSC 52 ; selection ; base64 content
+fn parse_reply(reply: &[u8]) -> Option<String> {
+    query::osc_sequences(reply).iter().find_map(|sequence| {
+        let (_selection, content) = sequence.strip_prefix("52;")?.split_once(';')?;
+        let content = STANDARD.decode(content).ok()?;
+        String::from_utf8(content).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    mod parse_reply {
+        use super::super::*;
+
+        #[test]
+        fn st_terminated_reply() {
+            assert_eq!(
+                parse_reply(b"\x1b]52;c;aGVsbG8=\x1b\\\x1b[?62;c"),
+                Some("hello".to_string())
+            );
+        }
+
+        #[test]
+        fn bel_terminated_reply() {
+            assert_eq!(
+                parse_reply(b"\x1b]52;p;aGVsbG8=\x07"),
+                Some("hello".to_string())
+            );
+        }
+
+        #[test]
+        fn no_reply() {
+            assert_eq!(parse_reply(b"\x1b[?62;c"), None);
+        }
+    }
+}
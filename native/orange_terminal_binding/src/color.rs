@@ -0,0 +1,398 @@
+use std::env;
+use std::sync::OnceLock;
+
+use crossterm::style::Color;
+use rustler::{Atom, Encoder, Env, Term, TermType};
+
+mod atoms {
+    rustler::atoms! {
+        rgb,
+        ansi256,
+        truecolor,
+        ansi16,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub fn to_atom(self) -> Atom {
+        match self {
+            ColorDepth::Ansi16 => atoms::ansi16(),
+            ColorDepth::Ansi256 => atoms::ansi256(),
+            ColorDepth::TrueColor => atoms::truecolor(),
+        }
+    }
+}
+
+static COLOR_DEPTH: OnceLock<ColorDepth> = OnceLock::new();
+
+// Detects how many colors the terminal can display. The result is cached since the
+// environment doesn't change during the lifetime of the VM.
+pub fn color_depth() -> ColorDepth {
+    *COLOR_DEPTH.get_or_init(|| {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        detect_color_depth(&colorterm, &term)
+    })
+}
+
+fn detect_color_depth(colorterm: &str, term: &str) -> ColorDepth {
+    if colorterm == "truecolor" || colorterm == "24bit" || term.ends_with("-direct") {
+        ColorDepth::TrueColor
+    } else if term.contains("256color") {
+        ColorDepth::Ansi256
+    } else if cfg!(windows) && term.is_empty() {
+        // The Windows console supports 24-bit colors since Windows 10, but doesn't set TERM
+        ColorDepth::TrueColor
+    } else {
+        ColorDepth::Ansi16
+    }
+}
+
+// Parses a color term. Supported formats are:
+// - named atoms, e.g. :red or :dark_grey
+// - {:rgb, r, g, b}
+// - {:ansi256, n}
+// - "#rrggbb" hex strings
+//
+// Returns None if the term is not a valid color.
+pub fn parse_color(term: Term) -> Option<Color> {
+    match term.get_type() {
+        TermType::Atom => named_color(&term.atom_to_string().ok()?),
+        TermType::Tuple => {
+            let elements = rustler::types::tuple::get_tuple(term).ok()?;
+            let tag: Atom = elements.first()?.decode().ok()?;
+
+            match elements.as_slice() {
+                [_, r, g, b] if tag == atoms::rgb() => Some(Color::Rgb {
+                    r: r.decode().ok()?,
+                    g: g.decode().ok()?,
+                    b: b.decode().ok()?,
+                }),
+                [_, n] if tag == atoms::ansi256() => Some(Color::AnsiValue(n.decode().ok()?)),
+                _ => None,
+            }
+        }
+        TermType::Binary => hex_color(&term.decode::<String>().ok()?),
+        _ => None,
+    }
+}
+
+const NAMED_COLORS: [(&str, Color); 16] = [
+    ("white", Color::White),
+    ("black", Color::Black),
+    ("grey", Color::Grey),
+    ("dark_grey", Color::DarkGrey),
+    ("red", Color::Red),
+    ("dark_red", Color::DarkRed),
+    ("green", Color::Green),
+    ("dark_green", Color::DarkGreen),
+    ("yellow", Color::Yellow),
+    ("dark_yellow", Color::DarkYellow),
+    ("blue", Color::Blue),
+    ("dark_blue", Color::DarkBlue),
+    ("magenta", Color::Magenta),
+    ("dark_magenta", Color::DarkMagenta),
+    ("cyan", Color::Cyan),
+    ("dark_cyan", Color::DarkCyan),
+];
+
+fn named_color(name: &str) -> Option<Color> {
+    NAMED_COLORS
+        .iter()
+        .find(|(color_name, _)| *color_name == name)
+        .map(|(_, color)| *color)
+}
+
+// Encodes the color in the same format parse_color accepts. Named colors are encoded as
+// atoms, indexed colors as {:ansi256, n} and the others as {:rgb, r, g, b}.
+pub fn encode_color(color: Color, env: Env) -> Term {
+    let name = NAMED_COLORS
+        .iter()
+        .find(|(_, named)| *named == color)
+        .map(|(name, _)| *name);
+
+    match (name, color) {
+        (Some(name), _) => Atom::from_str(env, name).unwrap().encode(env),
+        (None, Color::AnsiValue(n)) => (atoms::ansi256(), n).encode(env),
+        (None, Color::Rgb { r, g, b }) => (atoms::rgb(), r, g, b).encode(env),
+        // Reset isn't a color, the cell uses the default color
+        (None, _) => rustler::types::atom::nil().encode(env),
+    }
+}
+
+fn hex_color(value: &str) -> Option<Color> {
+    let hex = value.strip_prefix('#')?;
+
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+
+    Some(Color::Rgb {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+    })
+}
+
+// Parses an X11 color specification as used in OSC replies, i.e. rgb:r/g/b where each
+// channel has 1 to 4 hex digits
+pub fn parse_color_spec(spec: &str) -> Option<(u8, u8, u8)> {
+    let channels = spec.strip_prefix("rgb:")?.split('/').collect::<Vec<_>>();
+
+    let channel = |value: &str| {
+        if value.is_empty() || value.len() > 4 {
+            return None;
+        }
+
+        let max = (1u32 << (4 * value.len())) - 1;
+        let value = u32::from_str_radix(value, 16).ok()?;
+        Some((value * 255 / max) as u8)
+    };
+
+    match channels.as_slice() {
+        [r, g, b] => Some((channel(r)?, channel(g)?, channel(b)?)),
+        _ => None,
+    }
+}
+
+// The 16 named colors in ANSI order with their xterm default RGB values
+const ANSI16_PALETTE: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// The color of an indexed color SGR parameter. The first 16 are the named colors.
+pub fn ansi_color(index: u8) -> Color {
+    match ANSI16_PALETTE.get(index as usize) {
+        Some((color, _)) => *color,
+        None => Color::AnsiValue(index),
+    }
+}
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// Converts the color so that it can be displayed with the given color depth
+pub fn downsample(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (Color::Rgb { r, g, b }, ColorDepth::Ansi256) => Color::AnsiValue(rgb_to_ansi256(r, g, b)),
+        (Color::Rgb { r, g, b }, ColorDepth::Ansi16) => rgb_to_ansi16(r, g, b),
+        (Color::AnsiValue(n), ColorDepth::Ansi16) => {
+            let (r, g, b) = ansi256_to_rgb(n);
+            rgb_to_ansi16(r, g, b)
+        }
+        _ => color,
+    }
+}
+
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let cube_index = |value: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| (**level as i32 - value as i32).abs())
+            .map(|(index, _)| index as u8)
+            .unwrap()
+    };
+
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube_color = 16 + 36 * ri + 6 * gi + bi;
+    let cube_rgb = (
+        CUBE_LEVELS[ri as usize],
+        CUBE_LEVELS[gi as usize],
+        CUBE_LEVELS[bi as usize],
+    );
+
+    // The grayscale ramp goes from 8 to 238 in steps of 10
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_index;
+    let gray_color = 232 + gray_index;
+
+    if distance((r, g, b), (gray_level, gray_level, gray_level)) < distance((r, g, b), cube_rgb) {
+        gray_color
+    } else {
+        cube_color
+    }
+}
+
+fn rgb_to_ansi16(r: u8, g: u8, b: u8) -> Color {
+    ANSI16_PALETTE
+        .iter()
+        .min_by_key(|(_, rgb)| distance((r, g, b), *rgb))
+        .map(|(color, _)| *color)
+        .unwrap()
+}
+
+fn ansi256_to_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => ANSI16_PALETTE[n as usize].1,
+        16..=231 => {
+            let index = n - 16;
+            (
+                CUBE_LEVELS[(index / 36) as usize],
+                CUBE_LEVELS[(index / 6 % 6) as usize],
+                CUBE_LEVELS[(index % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (n - 232);
+            (level, level, level)
+        }
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let delta = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    delta(a.0, b.0) + delta(a.1, b.1) + delta(a.2, b.2)
+}
+
+#[cfg(test)]
+mod tests {
+    mod detect_color_depth {
+        use super::super::*;
+
+        #[test]
+        fn from_colorterm_and_term() {
+            assert_eq!(detect_color_depth("truecolor", ""), ColorDepth::TrueColor);
+            assert_eq!(
+                detect_color_depth("", "xterm-direct"),
+                ColorDepth::TrueColor
+            );
+            assert_eq!(
+                detect_color_depth("", "xterm-256color"),
+                ColorDepth::Ansi256
+            );
+            assert_eq!(detect_color_depth("", "xterm"), ColorDepth::Ansi16);
+        }
+
+        #[test]
+        fn without_term() {
+            let expected = if cfg!(windows) {
+                ColorDepth::TrueColor
+            } else {
+                ColorDepth::Ansi16
+            };
+
+            assert_eq!(detect_color_depth("", ""), expected);
+        }
+    }
+
+    mod hex_color {
+        use super::super::*;
+
+        #[test]
+        fn six_digits() {
+            assert_eq!(
+                hex_color("#ff8000"),
+                Some(Color::Rgb {
+                    r: 255,
+                    g: 128,
+                    b: 0
+                })
+            );
+        }
+
+        #[test]
+        fn invalid_hex() {
+            assert_eq!(hex_color("#GGGGGG"), None);
+            assert_eq!(hex_color("#fff"), None);
+            assert_eq!(hex_color("ff8000"), None);
+            assert_eq!(hex_color("#ff80é"), None);
+        }
+    }
+
+    mod rgb_to_ansi256 {
+        use super::super::*;
+
+        #[test]
+        fn cube_color() {
+            assert_eq!(rgb_to_ansi256(255, 0, 0), 196);
+            assert_eq!(rgb_to_ansi256(95, 135, 175), 67);
+        }
+
+        #[test]
+        fn grayscale_ramp_when_closer() {
+            // The closest cube color is 102, i.e. (135, 135, 135)
+            assert_eq!(rgb_to_ansi256(128, 128, 128), 244);
+        }
+    }
+
+    mod rgb_to_ansi16 {
+        use super::super::*;
+
+        #[test]
+        fn nearest_palette_color() {
+            assert_eq!(rgb_to_ansi16(250, 10, 10), Color::Red);
+            assert_eq!(rgb_to_ansi16(200, 0, 0), Color::DarkRed);
+            assert_eq!(rgb_to_ansi16(100, 100, 100), Color::DarkGrey);
+        }
+    }
+
+    mod downsample {
+        use super::super::*;
+
+        #[test]
+        fn to_color_depth() {
+            let orange = Color::Rgb {
+                r: 255,
+                g: 135,
+                b: 0,
+            };
+
+            assert_eq!(downsample(orange, ColorDepth::TrueColor), orange);
+            assert_eq!(
+                downsample(orange, ColorDepth::Ansi256),
+                Color::AnsiValue(208)
+            );
+            assert_eq!(
+                downsample(Color::AnsiValue(196), ColorDepth::Ansi16),
+                Color::Red
+            );
+            assert_eq!(downsample(Color::Blue, ColorDepth::Ansi16), Color::Blue);
+        }
+    }
+
+    mod parse_color_spec {
+        use super::super::*;
+
+        #[test]
+        fn four_digits_channels() {
+            assert_eq!(parse_color_spec("rgb:ffff/8080/0000"), Some((255, 128, 0)));
+        }
+
+        #[test]
+        fn two_digits_channels() {
+            assert_eq!(parse_color_spec("rgb:ff/80/00"), Some((255, 128, 0)));
+        }
+
+        #[test]
+        fn invalid_spec() {
+            assert_eq!(parse_color_spec("#ff8000"), None);
+            assert_eq!(parse_color_spec("rgb:ff/80"), None);
+            assert_eq!(parse_color_spec("rgb:fffff/0/0"), None);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::io;
+
+use rustler::{Atom, Encoder, Env, Error, NifResult, Term};
+
+mod atoms {
+    rustler::atoms! {
+        ok,
+    }
+}
+
+// The reason of a failed terminal operation. Well known errors are encoded as atoms,
+// the others as their error message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ErrorReason {
+    Atom(&'static str),
+    Message(String),
+}
+
+impl Encoder for ErrorReason {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            ErrorReason::Atom(name) => Atom::from_str(env, name).unwrap().encode(env),
+            ErrorReason::Message(message) => message.encode(env),
+        }
+    }
+}
+
+impl From<&io::Error> for ErrorReason {
+    fn from(err: &io::Error) -> Self {
+        #[cfg(unix)]
+        if err.raw_os_error() == Some(libc::ENOTTY) {
+            return ErrorReason::Atom("not_a_tty");
+        }
+
+        let name = match err.kind() {
+            io::ErrorKind::BrokenPipe => "broken_pipe",
+            io::ErrorKind::NotConnected => "not_connected",
+            io::ErrorKind::NotFound => "not_found",
+            io::ErrorKind::PermissionDenied => "permission_denied",
+            io::ErrorKind::Interrupted => "interrupted",
+            io::ErrorKind::TimedOut => "timed_out",
+            io::ErrorKind::UnexpectedEof => "unexpected_eof",
+            io::ErrorKind::Unsupported => "unsupported",
+            _ => return ErrorReason::Message(err.to_string()),
+        };
+
+        ErrorReason::Atom(name)
+    }
+}
+
+// Converts an IO error to a NIF error, which is returned as {:error, reason}
+pub fn io_error(err: io::Error) -> Error {
+    Error::Term(Box::new(ErrorReason::from(&err)))
+}
+
+// Returns :ok or {:error, reason}
+pub fn ok_result(result: io::Result<()>) -> NifResult<Atom> {
+    result.map(|_| atoms::ok()).map_err(io_error)
+}
+
+// Returns {:ok, value} or {:error, reason}
+pub fn value_result<T>(result: io::Result<T>) -> NifResult<(Atom, T)> {
+    result.map(|value| (atoms::ok(), value)).map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    mod error_reason {
+        use super::super::*;
+
+        #[test]
+        fn well_known_errors_are_atoms() {
+            let reason = |kind| ErrorReason::from(&io::Error::from(kind));
+
+            assert_eq!(
+                reason(io::ErrorKind::BrokenPipe),
+                ErrorReason::Atom("broken_pipe")
+            );
+            assert_eq!(
+                reason(io::ErrorKind::PermissionDenied),
+                ErrorReason::Atom("permission_denied")
+            );
+            assert_eq!(
+                reason(io::ErrorKind::Unsupported),
+                ErrorReason::Atom("unsupported")
+            );
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn not_a_tty() {
+            let err = io::Error::from_raw_os_error(libc::ENOTTY);
+            assert_eq!(ErrorReason::from(&err), ErrorReason::Atom("not_a_tty"));
+        }
+
+        #[test]
+        fn other_errors_are_messages() {
+            let err = io::Error::other("terminal is gone");
+            assert_eq!(
+                ErrorReason::from(&err),
+                ErrorReason::Message("terminal is gone".to_string())
+            );
+        }
+    }
+}
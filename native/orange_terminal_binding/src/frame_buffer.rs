@@ -0,0 +1,222 @@
+use std::sync::Mutex;
+
+use crate::render::StyledCell;
+
+pub type Frame = Vec<Vec<StyledCell>>;
+
+// Keeps the last presented frame, so that only the changed cells are written to the
+// terminal on the next present.
+pub struct FrameBuffer {
+    previous: Mutex<Option<Frame>>,
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        FrameBuffer {
+            previous: Mutex::new(None),
+        }
+    }
+
+    // Forgets the last frame. The next present will redraw every cell.
+    pub fn invalidate(&self) {
+        *self.previous.lock().unwrap() = None;
+    }
+
+    // Calls `write` with the coordinates of the cells which differ from the previous frame,
+    // then remembers `frame` as the previous frame. If `write` fails, the terminal content is
+    // unknown and the frame buffer is invalidated.
+    pub fn present<E>(
+        &self,
+        frame: Frame,
+        write: impl FnOnce(&Frame, Vec<(usize, usize)>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut previous = self.previous.lock().unwrap();
+        let changed = changed_cells(previous.as_ref(), &frame);
+
+        match write(&frame, changed) {
+            Ok(()) => {
+                *previous = Some(frame);
+                Ok(())
+            }
+            Err(err) => {
+                *previous = None;
+                Err(err)
+            }
+        }
+    }
+}
+
+fn changed_cells(previous: Option<&Frame>, frame: &Frame) -> Vec<(usize, usize)> {
+    let mut changed = Vec::new();
+
+    for (y, row) in frame.iter().enumerate() {
+        // Without a previous row of the same width, redraw the whole row
+        let previous_row = previous
+            .and_then(|previous| previous.get(y))
+            .filter(|previous_row| previous_row.len() == row.len());
+
+        let Some(previous_row) = previous_row else {
+            changed.extend((0..row.len()).map(|x| (x, y)));
+            continue;
+        };
+
+        let changed_x = (0..row.len()).filter(|&x| previous_row[x] != row[x]);
+
+        // Wide cells shift the rest of the row on the terminal, so writing a single cell
+        // after them would land on the wrong column. Redraw the whole row instead.
+        let has_wide_cells = row.iter().chain(previous_row).any(StyledCell::is_wide);
+
+        if has_wide_cells && changed_x.clone().next().is_some() {
+            changed.extend((0..row.len()).map(|x| (x, y)));
+        } else {
+            changed.extend(changed_x.map(|x| (x, y)));
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    mod changed_cells {
+        use super::super::*;
+
+        fn row(characters: &[&str]) -> Vec<StyledCell> {
+            characters
+                .iter()
+                .map(|character| StyledCell {
+                    character: character.to_string(),
+                    ..StyledCell::blank()
+                })
+                .collect()
+        }
+
+        #[test]
+        fn without_previous_frame() {
+            let frame = vec![row(&["a", "b"])];
+            assert_eq!(changed_cells(None, &frame), vec![(0, 0), (1, 0)]);
+        }
+
+        #[test]
+        fn only_changed_cells() {
+            let previous = vec![row(&["a", "b", "c"])];
+            let frame = vec![row(&["a", "x", "c"])];
+            assert_eq!(changed_cells(Some(&previous), &frame), vec![(1, 0)]);
+        }
+
+        #[test]
+        fn style_changes() {
+            let previous = vec![row(&["a", "b"])];
+            let mut frame = previous.clone();
+            frame[0][1].style.foreground_color = Some(crossterm::style::Color::Red);
+
+            assert_eq!(changed_cells(Some(&previous), &frame), vec![(1, 0)]);
+        }
+
+        #[test]
+        fn redraws_resized_rows() {
+            let previous = vec![row(&["a", "b"]), row(&["c", "d"])];
+            let frame = vec![row(&["a", "b", "e"]), row(&["c", "d"]), row(&["f"])];
+
+            assert_eq!(
+                changed_cells(Some(&previous), &frame),
+                vec![(0, 0), (1, 0), (2, 0), (0, 2)]
+            );
+        }
+
+        #[test]
+        fn keeps_cells_after_wide_cells() {
+            let frame = vec![row(&["漢", "字", "a"])];
+            assert_eq!(changed_cells(None, &frame), vec![(0, 0), (1, 0), (2, 0)]);
+        }
+
+        #[test]
+        fn redraws_rows_with_wide_cells() {
+            let previous = vec![row(&["漢", "x", "a"]), row(&["漢", "y"])];
+            let frame = vec![row(&["漢", "x", "b"]), row(&["漢", "y"])];
+
+            assert_eq!(
+                changed_cells(Some(&previous), &frame),
+                vec![(0, 0), (1, 0), (2, 0)]
+            );
+        }
+
+        #[test]
+        fn redraws_rows_which_had_wide_cells() {
+            let previous = vec![row(&["漢", "x", "a"])];
+            let frame = vec![row(&["b", "x", "a"])];
+
+            assert_eq!(
+                changed_cells(Some(&previous), &frame),
+                vec![(0, 0), (1, 0), (2, 0)]
+            );
+        }
+    }
+
+    mod present {
+        use super::super::*;
+
+        fn frame(character: &str) -> Frame {
+            vec![vec![StyledCell {
+                character: character.to_string(),
+                ..StyledCell::blank()
+            }]]
+        }
+
+        // Presents the frame and returns the changed cells
+        fn present(frame_buffer: &FrameBuffer, frame: Frame) -> Vec<(usize, usize)> {
+            let mut written = Vec::new();
+            frame_buffer
+                .present(frame, |_, changed| {
+                    written = changed;
+                    Ok::<(), ()>(())
+                })
+                .unwrap();
+            written
+        }
+
+        #[test]
+        fn skips_unchanged_frames() {
+            let frame_buffer = FrameBuffer::new();
+
+            assert_eq!(present(&frame_buffer, frame("a")), vec![(0, 0)]);
+            assert_eq!(present(&frame_buffer, frame("a")), vec![]);
+        }
+
+        #[test]
+        fn unchanged_frames_write_nothing() {
+            let frame_buffer = FrameBuffer::new();
+            let write = |frame| {
+                let mut output = Vec::new();
+                frame_buffer
+                    .present(frame, |frame, changed| {
+                        let cells = changed.into_iter().map(|(x, y)| (&frame[y][x], x, y));
+                        crate::render::write_cells(&mut output, cells)
+                    })
+                    .unwrap();
+                output
+            };
+
+            assert!(!write(frame("a")).is_empty());
+            assert!(write(frame("a")).is_empty());
+        }
+
+        #[test]
+        fn invalidate_redraws_every_cell() {
+            let frame_buffer = FrameBuffer::new();
+            present(&frame_buffer, frame("a"));
+
+            frame_buffer.invalidate();
+            assert_eq!(present(&frame_buffer, frame("a")), vec![(0, 0)]);
+        }
+
+        #[test]
+        fn invalidates_when_the_write_fails() {
+            let frame_buffer = FrameBuffer::new();
+            present(&frame_buffer, frame("a"));
+
+            assert_eq!(frame_buffer.present(frame("b"), |_, _| Err(())), Err(()));
+            assert_eq!(present(&frame_buffer, frame("b")), vec![(0, 0)]);
+        }
+    }
+}
@@ -0,0 +1,569 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+use crossterm::event::{
+    EnableBracketedPaste, EnableFocusChange, EnableMouseCapture, KeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{self, EnterAlternateScreen, SetTitle};
+use crossterm::{cursor, execute};
+
+use crate::render::{flush, queue_command};
+use crate::{output, viewport};
+
+// Terminal modes enabled by the binding. They are remembered, so that they can be undone
+// even if the application doesn't restore the terminal itself, e.g. the VM crashes.
+#[derive(Debug, Clone, Copy)]
+pub enum Mode {
+    Raw = 1 << 0,
+    AlternateScreen = 1 << 1,
+    HiddenCursor = 1 << 2,
+    CursorShape = 1 << 3,
+    MouseCapture = 1 << 4,
+    BracketedPaste = 1 << 5,
+    FocusReporting = 1 << 6,
+    SynchronizedOutput = 1 << 7,
+}
+
+static MODES: AtomicU32 = AtomicU32::new(0);
+static KEYBOARD_ENHANCEMENT_PUSHES: AtomicU32 = AtomicU32::new(0);
+static TITLE_PUSHES: AtomicU32 = AtomicU32::new(0);
+
+// What is needed to enable the modes again after a suspend: the DECSCUSR parameter of the
+// cursor shape, the pushed keyboard enhancement flags and the last title set
+static CURSOR_SHAPE: AtomicU8 = AtomicU8::new(0);
+static KEYBOARD_ENHANCEMENT_FLAGS: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+static TITLE: Mutex<Option<String>> = Mutex::new(None);
+
+// Set when the process is resumed after a suspend, until the resume event is reported
+static RESUMED: AtomicBool = AtomicBool::new(false);
+
+// The output file descriptor, used when the terminal is restored from a signal handler
+static OUTPUT_FD: AtomicI32 = AtomicI32::new(1);
+
+pub fn enable(mode: Mode) {
+    MODES.fetch_or(mode as u32, Ordering::AcqRel);
+}
+
+pub fn disable(mode: Mode) {
+    MODES.fetch_and(!(mode as u32), Ordering::AcqRel);
+}
+
+// `shape` is the DECSCUSR parameter, 0 being the default shape
+pub fn set_cursor_shape(shape: u8) {
+    CURSOR_SHAPE.store(shape, Ordering::Release);
+
+    match shape {
+        0 => disable(Mode::CursorShape),
+        _ => enable(Mode::CursorShape),
+    }
+}
+
+pub fn push_keyboard_enhancement(flags: KeyboardEnhancementFlags) {
+    KEYBOARD_ENHANCEMENT_FLAGS
+        .lock()
+        .unwrap()
+        .push(flags.bits());
+    KEYBOARD_ENHANCEMENT_PUSHES.fetch_add(1, Ordering::AcqRel);
+}
+
+pub fn pop_keyboard_enhancement() {
+    KEYBOARD_ENHANCEMENT_FLAGS.lock().unwrap().pop();
+    decrement(&KEYBOARD_ENHANCEMENT_PUSHES);
+}
+
+pub fn set_title(title: String) {
+    *TITLE.lock().unwrap() = Some(title);
+}
+
+pub fn push_title() {
+    TITLE_PUSHES.fetch_add(1, Ordering::AcqRel);
+}
+
+pub fn pop_title() {
+    decrement(&TITLE_PUSHES);
+}
+
+pub fn set_output_fd(fd: i32) {
+    OUTPUT_FD.store(fd, Ordering::Release);
+}
+
+fn decrement(counter: &AtomicU32) {
+    let _ = counter.fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+        count.checked_sub(1)
+    });
+}
+
+// Undoes every mode enabled by the binding and leaves the inline viewport
+pub fn restore() -> io::Result<()> {
+    restore_with(&mut output::writer(), terminal::disable_raw_mode)
+}
+
+// The modes are forgotten before they are undone, so nothing would restore raw mode later.
+// It's disabled even if the writes fail, e.g. the output is a closed pipe, otherwise the
+// shell would be left in raw mode.
+fn restore_with(
+    writer: &mut impl Write,
+    disable_raw_mode: impl FnOnce() -> io::Result<()>,
+) -> io::Result<()> {
+    let modes = MODES.swap(0, Ordering::AcqRel);
+    KEYBOARD_ENHANCEMENT_FLAGS.lock().unwrap().clear();
+
+    let written = write_undo_sequences(writer, modes);
+
+    let raw_mode_disabled = match modes & Mode::Raw as u32 {
+        0 => Ok(()),
+        _ => disable_raw_mode(),
+    };
+
+    written.and(raw_mode_disabled)
+}
+
+fn write_undo_sequences(writer: &mut impl Write, modes: u32) -> io::Result<()> {
+    for sequence in undo_sequences(modes) {
+        queue_command(writer, Print(sequence))?;
+    }
+
+    viewport::leave(writer)?;
+    flush(writer)
+}
+
+// The modes enabled before a suspend
+struct Snapshot {
+    modes: u32,
+    cursor_shape: u8,
+    keyboard_enhancement_flags: Vec<u8>,
+    titles: u32,
+    title: Option<String>,
+    viewport_height: Option<u16>,
+}
+
+impl Snapshot {
+    fn take() -> Self {
+        Snapshot {
+            modes: MODES.load(Ordering::Acquire),
+            cursor_shape: CURSOR_SHAPE.load(Ordering::Acquire),
+            keyboard_enhancement_flags: KEYBOARD_ENHANCEMENT_FLAGS.lock().unwrap().clone(),
+            titles: TITLE_PUSHES.load(Ordering::Acquire),
+            title: TITLE.lock().unwrap().clone(),
+            viewport_height: viewport::height(),
+        }
+    }
+
+    fn has(&self, mode: Mode) -> bool {
+        self.modes & mode as u32 != 0
+    }
+
+    // Enables the modes again, in the order the runtime enables them
+    fn apply(self) -> io::Result<()> {
+        let mut writer = output::writer();
+
+        if self.has(Mode::SynchronizedOutput) {
+            enable(Mode::SynchronizedOutput);
+        }
+
+        if self.has(Mode::Raw) {
+            terminal::enable_raw_mode()?;
+            enable(Mode::Raw);
+        }
+
+        if self.has(Mode::AlternateScreen) {
+            execute!(writer, EnterAlternateScreen)?;
+            enable(Mode::AlternateScreen);
+        }
+
+        if let Some(height) = self.viewport_height {
+            viewport::enter(&mut writer, height)?;
+        }
+
+        if self.has(Mode::HiddenCursor) {
+            execute!(writer, cursor::Hide)?;
+            enable(Mode::HiddenCursor);
+        }
+
+        if self.has(Mode::CursorShape) {
+            execute!(writer, Print(format!("\x1b[{} q", self.cursor_shape)))?;
+            set_cursor_shape(self.cursor_shape);
+        }
+
+        if self.has(Mode::MouseCapture) {
+            execute!(writer, EnableMouseCapture)?;
+            enable(Mode::MouseCapture);
+        }
+
+        if self.has(Mode::BracketedPaste) {
+            execute!(writer, EnableBracketedPaste)?;
+            enable(Mode::BracketedPaste);
+        }
+
+        if self.has(Mode::FocusReporting) {
+            execute!(writer, EnableFocusChange)?;
+            enable(Mode::FocusReporting);
+        }
+
+        for bits in self.keyboard_enhancement_flags {
+            let flags = KeyboardEnhancementFlags::from_bits_truncate(bits);
+            execute!(writer, PushKeyboardEnhancementFlags(flags))?;
+            push_keyboard_enhancement(flags);
+        }
+
+        for _ in 0..self.titles {
+            execute!(writer, Print("\x1b[22;0t"))?;
+            push_title();
+        }
+
+        if let Some(title) = self.title {
+            execute!(writer, SetTitle(title))?;
+        }
+
+        Ok(())
+    }
+}
+
+// Gives the terminal back to the shell and stops the process with SIGTSTP, the same as
+// Ctrl+Z does in cooked mode. Returns once the process is continued, after the modes are
+// enabled again. The terminal content is lost, so the next poll reports a resume event
+// for the application to repaint.
+#[cfg(unix)]
+pub fn suspend() -> io::Result<()> {
+    let snapshot = Snapshot::take();
+
+    restore()?;
+    unix::stop()?;
+
+    let result = snapshot.apply();
+    RESUMED.store(true, Ordering::Release);
+    result
+}
+
+#[cfg(not(unix))]
+pub fn suspend() -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// Whether the process was resumed since the last call
+pub fn take_resumed() -> bool {
+    RESUMED.swap(false, Ordering::AcqRel)
+}
+
+// The sequences which undo the modes, in the reverse order they are usually enabled.
+// Allocation free, since it's used in signal handlers.
+fn undo_sequences(modes: u32) -> impl Iterator<Item = &'static str> {
+    let enabled = move |mode: Mode| modes & mode as u32 != 0;
+
+    let titles = TITLE_PUSHES.swap(0, Ordering::AcqRel) as usize;
+    let keyboard_enhancements = KEYBOARD_ENHANCEMENT_PUSHES.swap(0, Ordering::AcqRel) as usize;
+
+    let sequences = [
+        // A frame might have been interrupted
+        (enabled(Mode::SynchronizedOutput), "\x1b[?2026l"),
+        (modes != 0, "\x1b[0m"),
+        (
+            enabled(Mode::MouseCapture),
+            "\x1b[?1006l\x1b[?1015l\x1b[?1003l\x1b[?1002l\x1b[?1000l",
+        ),
+        (enabled(Mode::BracketedPaste), "\x1b[?2004l"),
+        (enabled(Mode::FocusReporting), "\x1b[?1004l"),
+        (enabled(Mode::CursorShape), "\x1b[0 q"),
+        (enabled(Mode::HiddenCursor), "\x1b[?25h"),
+        (enabled(Mode::AlternateScreen), "\x1b[?1049l"),
+    ];
+
+    std::iter::repeat_n("\x1b[23;0t", titles)
+        .chain(std::iter::repeat_n("\x1b[<1u", keyboard_enhancements))
+        .chain(
+            sequences
+                .into_iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, sequence)| sequence),
+        )
+}
+
+// Restores the terminal if the process dies from a fatal signal or exits, and when the NIF
+// library is unloaded. Panics are left alone: rustler turns NIF panics into exceptions and
+// the VM keeps running, and panics which abort raise SIGABRT. The library is loaded again on
+// upgrades, but the handlers are installed once.
+pub fn install() {
+    #[cfg(unix)]
+    unix::install();
+}
+
+#[cfg(unix)]
+pub use unix::save_termios;
+
+#[cfg(not(unix))]
+pub fn save_termios() {}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::IntoRawFd;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::{Once, OnceLock};
+
+    use super::{undo_sequences, Mode, MODES, OUTPUT_FD};
+
+    // The terminal attributes before raw mode was enabled, and a descriptor of the terminal
+    // to restore them on. crossterm keeps them behind a lock, which can't be used in signal
+    // handlers.
+    static ORIGINAL_TERMIOS: OnceLock<libc::termios> = OnceLock::new();
+    static TTY_FD: AtomicI32 = AtomicI32::new(-1);
+
+    const SIGNALS: [libc::c_int; 8] = [
+        libc::SIGTERM,
+        libc::SIGHUP,
+        libc::SIGQUIT,
+        libc::SIGABRT,
+        libc::SIGSEGV,
+        libc::SIGBUS,
+        libc::SIGFPE,
+        libc::SIGILL,
+    ];
+
+    static PREVIOUS_ACTIONS: [OnceLock<libc::sigaction>; SIGNALS.len()] =
+        [const { OnceLock::new() }; SIGNALS.len()];
+
+    // Must be called before raw mode is enabled. Same as crossterm, the terminal is stdin if
+    // it's a TTY, otherwise /dev/tty.
+    pub fn save_termios() {
+        if ORIGINAL_TERMIOS.get().is_some() {
+            return;
+        }
+
+        let fd = if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+            libc::STDIN_FILENO
+        } else {
+            match File::options().read(true).write(true).open("/dev/tty") {
+                // Kept open until the process exits
+                Ok(file) => file.into_raw_fd(),
+                Err(_) => return,
+            }
+        };
+
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+
+        if unsafe { libc::tcgetattr(fd, &mut termios) } == 0 {
+            TTY_FD.store(fd, Ordering::Release);
+            let _ = ORIGINAL_TERMIOS.set(termios);
+        }
+    }
+
+    // Stops the process until SIGCONT. The VM might handle or ignore SIGTSTP, and block it
+    // in its threads, so the default action is used for the duration of the call.
+    pub fn stop() -> io::Result<()> {
+        unsafe {
+            let mut action = std::mem::zeroed::<libc::sigaction>();
+            action.sa_sigaction = libc::SIG_DFL;
+            libc::sigemptyset(&mut action.sa_mask);
+
+            let mut previous_action = std::mem::zeroed::<libc::sigaction>();
+
+            if libc::sigaction(libc::SIGTSTP, &action, &mut previous_action) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut signals = std::mem::zeroed::<libc::sigset_t>();
+            libc::sigemptyset(&mut signals);
+            libc::sigaddset(&mut signals, libc::SIGTSTP);
+
+            let mut previous_mask = std::mem::zeroed::<libc::sigset_t>();
+            libc::pthread_sigmask(libc::SIG_UNBLOCK, &signals, &mut previous_mask);
+
+            // The signal is delivered to this thread before raise returns, which stops the
+            // whole process
+            let result = match libc::raise(libc::SIGTSTP) {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            };
+
+            libc::pthread_sigmask(libc::SIG_SETMASK, &previous_mask, std::ptr::null_mut());
+            libc::sigaction(libc::SIGTSTP, &previous_action, std::ptr::null_mut());
+
+            result
+        }
+    }
+
+    // Only uses async-signal-safe functions
+    fn restore_from_signal() {
+        let modes = MODES.swap(0, Ordering::AcqRel);
+        let fd = OUTPUT_FD.load(Ordering::Acquire);
+
+        for sequence in undo_sequences(modes) {
+            write_all(fd, sequence.as_bytes());
+        }
+
+        if modes & Mode::Raw as u32 != 0 {
+            if let Some(termios) = ORIGINAL_TERMIOS.get() {
+                unsafe { libc::tcsetattr(TTY_FD.load(Ordering::Acquire), libc::TCSANOW, termios) };
+            }
+        }
+    }
+
+    fn write_all(fd: libc::c_int, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            match unsafe { libc::write(fd, bytes.as_ptr() as *const _, bytes.len()) } {
+                count if count > 0 => bytes = &bytes[count as usize..],
+                _ if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted => {
+                    continue
+                }
+                _ => return,
+            }
+        }
+    }
+
+    // Keeps the flags of the previous handler, e.g. SA_RESTART and SA_ONSTACK, which the
+    // VM relies on
+    pub fn install() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(install_handlers);
+    }
+
+    fn install_handlers() {
+        for (index, signal) in SIGNALS.iter().enumerate() {
+            unsafe {
+                let mut previous = std::mem::zeroed::<libc::sigaction>();
+
+                if libc::sigaction(*signal, std::ptr::null(), &mut previous) != 0 {
+                    continue;
+                }
+
+                let mut action = std::mem::zeroed::<libc::sigaction>();
+                action.sa_sigaction = handle_signal as *const () as usize;
+                action.sa_flags = previous.sa_flags | libc::SA_SIGINFO;
+                libc::sigemptyset(&mut action.sa_mask);
+
+                if libc::sigaction(*signal, &action, std::ptr::null_mut()) == 0 {
+                    let _ = PREVIOUS_ACTIONS[index].set(previous);
+                }
+            }
+        }
+    }
+
+    // Restores the terminal, then hands the signal to the previous handler. The VM handles
+    // SIGTERM by stopping gracefully, and the runtime restores the terminal once it's done
+    // drawing, so the terminal is only restored here if the signal kills the process.
+    extern "C" fn handle_signal(
+        signal: libc::c_int,
+        info: *mut libc::siginfo_t,
+        context: *mut libc::c_void,
+    ) {
+        let previous = SIGNALS
+            .iter()
+            .position(|s| *s == signal)
+            .and_then(|index| PREVIOUS_ACTIONS[index].get());
+
+        let is_graceful = signal == libc::SIGTERM
+            && previous.is_some_and(|previous| {
+                previous.sa_sigaction != libc::SIG_DFL && previous.sa_sigaction != libc::SIG_IGN
+            });
+
+        if !is_graceful {
+            restore_from_signal();
+        }
+
+        unsafe {
+            match previous {
+                Some(previous) if previous.sa_sigaction == libc::SIG_IGN => (),
+                Some(previous) if previous.sa_sigaction != libc::SIG_DFL => {
+                    if previous.sa_flags & libc::SA_SIGINFO != 0 {
+                        let handler: extern "C" fn(
+                            libc::c_int,
+                            *mut libc::siginfo_t,
+                            *mut libc::c_void,
+                        ) = std::mem::transmute(previous.sa_sigaction);
+                        handler(signal, info, context);
+                    } else {
+                        let handler: extern "C" fn(libc::c_int) =
+                            std::mem::transmute(previous.sa_sigaction);
+                        handler(signal);
+                    }
+                }
+                // Die with the default action
+                _ => {
+                    libc::signal(signal, libc::SIG_DFL);
+                    libc::raise(signal);
+                }
+            }
+        }
+    }
+
+    // Runs when the process exits or the library is unloaded, like a C destructor
+    extern "C" fn restore_on_unload() {
+        restore_from_signal();
+    }
+
+    #[used]
+    #[cfg_attr(
+        any(target_os = "linux", target_os = "freebsd", target_os = "android"),
+        link_section = ".fini_array"
+    )]
+    #[cfg_attr(target_os = "macos", link_section = "__DATA,__mod_term_func")]
+    static RESTORE_ON_UNLOAD: extern "C" fn() = restore_on_unload;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    // The modes are global, so the tests which change them don't run concurrently
+    static MODES_LOCK: Mutex<()> = Mutex::new(());
+
+    mod undo_sequences {
+        use super::super::*;
+        use super::MODES_LOCK;
+
+        #[test]
+        fn undoes_enabled_modes_and_pushes() {
+            let _lock = MODES_LOCK.lock().unwrap();
+            assert_eq!(undo_sequences(0).count(), 0);
+
+            push_title();
+            push_keyboard_enhancement(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES);
+            push_keyboard_enhancement(KeyboardEnhancementFlags::REPORT_EVENT_TYPES);
+            pop_keyboard_enhancement();
+
+            let modes = Mode::AlternateScreen as u32 | Mode::HiddenCursor as u32;
+            let sequences = undo_sequences(modes).collect::<String>();
+
+            assert_eq!(sequences, "\x1b[23;0t\x1b[<1u\x1b[0m\x1b[?25h\x1b[?1049l");
+            assert_eq!(undo_sequences(0).count(), 0);
+        }
+    }
+
+    mod restore_with {
+        use super::super::*;
+        use super::MODES_LOCK;
+
+        struct ClosedPipe;
+
+        impl Write for ClosedPipe {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::BrokenPipe))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::Error::from(io::ErrorKind::BrokenPipe))
+            }
+        }
+
+        #[test]
+        fn disables_raw_mode_when_writes_fail() {
+            let _lock = MODES_LOCK.lock().unwrap();
+            enable(Mode::Raw);
+            enable(Mode::HiddenCursor);
+
+            let mut raw_mode_disabled = false;
+            let result = restore_with(&mut ClosedPipe, || {
+                raw_mode_disabled = true;
+                Ok(())
+            });
+
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+            assert!(raw_mode_disabled);
+            assert_eq!(MODES.load(Ordering::Acquire), 0);
+        }
+    }
+}
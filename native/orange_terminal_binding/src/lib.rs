@@ -1,124 +1,242 @@
-use std::io::{self, Write};
+mod clipboard;
+mod color;
+mod error;
+mod frame_buffer;
+mod guard;
+mod output;
+mod query;
+mod recording;
+mod render;
+mod viewport;
+mod virtual_terminal;
 
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
-use crossterm::style::{
-    Attribute, Attributes, Color, ContentStyle, PrintStyledContent, StyledContent,
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clipboard::ClipboardTarget;
+use crossterm::event::{
+    self, DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+    EnableFocusChange, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyEventState,
+    KeyModifiers, KeyboardEnhancementFlags, MouseButton, MouseEventKind,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::style::{Attribute, Attributes, Color, ContentStyle};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen, SetTitle};
+use crossterm::{cursor, execute};
+use error::{io_error, ok_result, value_result, ErrorReason};
+use frame_buffer::FrameBuffer;
+use guard::Mode;
+use output::OutputTarget;
+use render::{Hyperlink, StyledCell};
+use rustler::{
+    Atom, Binary, Encoder, Env, Error, LocalPid, NewBinary, NifMap, NifResult, NifStruct,
+    NifUnitEnum, OwnedEnv, ResourceArc, Term,
 };
-use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
-use crossterm::{cursor, execute, queue};
-use rustler::{Atom, Encoder, Env, NifStruct, Term};
+use virtual_terminal::VirtualTerminal;
 
-#[derive(Debug, NifStruct)]
-#[module = "Orange.Renderer.Buffer"]
-struct Buffer {
-    rows: Vec<Vec<Option<Cell>>>,
+mod atoms {
+    rustler::atoms! {
+        ok,
+        invalid_color,
+        invalid_hyperlink,
+        invalid_text_modifier,
+        timeout,
+        event,
+        event_reader_error,
+        rgb,
+    }
 }
 
 #[derive(Debug, NifStruct)]
 #[module = "Orange.Renderer.Cell"]
-struct Cell {
+struct Cell<'a> {
     character: String,
-    foreground: Option<Atom>,
-    background: Option<Atom>,
+    foreground: Option<Term<'a>>,
+    background: Option<Term<'a>>,
+    underline_color: Option<Term<'a>>,
     modifiers: Vec<Atom>,
+    hyperlink: Option<Term<'a>>,
 }
 
 #[rustler::nif]
-fn draw(env: Env, cells: Vec<(Cell, usize, usize)>) {
-    let mut writer = io::stdout();
-    let mut previous_cursor: Option<(u16, u16)> = None;
+fn draw<'a>(env: Env<'a>, cells: Vec<(Cell<'a>, usize, usize)>) -> NifResult<Atom> {
+    let cells = styled_cells(&cells, env)?;
 
-    for (cell, x, y) in cells.iter() {
-        let should_move = match previous_cursor {
-            Some((px, py)) => px != (*x - 1) as u16 || py != *y as u16,
-            None => true,
-        };
+    let mut writer = output::writer();
+    ok_result(render::write_cells(
+        &mut writer,
+        cells.iter().map(|(cell, x, y)| (cell, *x, *y)),
+    ))
+}
 
-        if should_move {
-            queue_command(&mut writer, cursor::MoveTo(*x as u16, *y as u16));
-        }
+// Returns the bytes `draw` would write for the cells, instead of writing them to the
+// terminal output
+#[rustler::nif]
+fn render_to_binary<'a>(
+    env: Env<'a>,
+    cells: Vec<(Cell<'a>, usize, usize)>,
+) -> NifResult<Binary<'a>> {
+    let cells = styled_cells(&cells, env)?;
+
+    let mut bytes = Vec::new();
+    render::write_cells(&mut bytes, cells.iter().map(|(cell, x, y)| (cell, *x, *y)))
+        .map_err(io_error)?;
+
+    let mut binary = NewBinary::new(env, bytes.len());
+    binary.as_mut_slice().copy_from_slice(&bytes);
+    Ok(binary.into())
+}
+
+fn styled_cells(
+    cells: &[(Cell, usize, usize)],
+    env: Env,
+) -> NifResult<Vec<(StyledCell, usize, usize)>> {
+    cells
+        .iter()
+        .map(|(cell, x, y)| Ok((styled_cell(cell, env)?, *x, *y)))
+        .collect()
+}
+
+#[rustler::nif]
+fn new_frame_buffer() -> ResourceArc<FrameBuffer> {
+    ResourceArc::new(FrameBuffer::new())
+}
+
+// Draws the rows, writing only the cells which changed since the last present. A cell
+// is either a %Cell{} or nil/:undefined for an empty cell.
+#[rustler::nif]
+fn present<'a>(
+    env: Env<'a>,
+    frame_buffer: ResourceArc<FrameBuffer>,
+    rows: Vec<Vec<Term<'a>>>,
+) -> NifResult<Atom> {
+    let frame = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|term| {
+                    if term.is_atom() {
+                        Ok(StyledCell::blank())
+                    } else {
+                        styled_cell(&term.decode()?, env)
+                    }
+                })
+                .collect::<NifResult<Vec<_>>>()
+        })
+        .collect::<NifResult<Vec<_>>>()?;
 
-        let content = StyledContent::new(content_style_from_cell(cell, env), &cell.character);
-        queue_command(&mut writer, PrintStyledContent(content));
+    let mut writer = output::writer();
+    ok_result(frame_buffer.present(frame, |frame, changed| {
+        render::write_cells(
+            &mut writer,
+            changed.into_iter().map(|(x, y)| (&frame[y][x], x, y)),
+        )
+    }))
+}
+
+#[rustler::nif]
+fn invalidate(frame_buffer: ResourceArc<FrameBuffer>) -> Atom {
+    frame_buffer.invalidate();
+    render::reset_cursor_position();
+    atoms::ok()
+}
+
+fn styled_cell(cell: &Cell, env: Env) -> NifResult<StyledCell> {
+    Ok(StyledCell {
+        character: cell.character.clone(),
+        style: content_style_from_cell(cell, env)?,
+        hyperlink: cell.hyperlink.map(term_to_hyperlink).transpose()?,
+    })
+}
 
-        previous_cursor = Some((*x as u16, *y as u16));
+// A hyperlink is either an URI or an {uri, id} tuple
+fn term_to_hyperlink(term: Term) -> NifResult<Hyperlink> {
+    if let Ok(uri) = term.decode::<String>() {
+        return Ok(Hyperlink { uri, id: None });
     }
 
-    flush(&mut writer);
+    match term.decode::<(String, String)>() {
+        Ok((uri, id)) => Ok(Hyperlink { uri, id: Some(id) }),
+        Err(_) => Err(Error::RaiseTerm(Box::new((
+            atoms::invalid_hyperlink(),
+            format!("{:?}", term),
+        )))),
+    }
 }
 
-fn content_style_from_cell(cell: &Cell, env: Env) -> ContentStyle {
+fn content_style_from_cell(cell: &Cell, env: Env) -> NifResult<ContentStyle> {
     let mut style = ContentStyle::new();
-    style.foreground_color = cell
-        .foreground
-        .as_ref()
-        .map(|color| atom_to_color(*color, env));
-    style.background_color = cell
-        .background
-        .as_ref()
-        .map(|color| atom_to_color(*color, env));
+    style.foreground_color = cell.foreground.map(term_to_color).transpose()?;
+    style.background_color = cell.background.map(term_to_color).transpose()?;
+    style.underline_color = cell.underline_color.map(term_to_color).transpose()?;
     style.attributes = Attributes::from(
         cell.modifiers
             .iter()
             .map(|modifier| atom_to_text_attribute(*modifier, env))
-            .collect::<Vec<Attribute>>()
+            .collect::<NifResult<Vec<Attribute>>>()?
             .as_slice(),
     );
 
-    style
+    Ok(style)
 }
 
-fn queue_command(writer: &mut impl Write, command: impl crossterm::Command + Clone) {
-    loop {
-        match queue!(writer, command.clone()) {
-            Ok(_) => break,
-            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
-            Err(err) => panic!("{}", err),
-        }
+fn term_to_color(term: Term) -> NifResult<Color> {
+    match color::parse_color(term) {
+        Some(color) => Ok(color::downsample(color, color::color_depth())),
+        None => Err(Error::RaiseTerm(Box::new((
+            atoms::invalid_color(),
+            format!("{:?}", term),
+        )))),
     }
 }
 
-fn flush(writer: &mut impl Write) {
-    loop {
-        match writer.flush() {
-            Ok(_) => break,
-            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
-            Err(err) => panic!("{}", err),
-        }
+const TEXT_ATTRIBUTES: [(&str, Attribute); 14] = [
+    ("bold", Attribute::Bold),
+    ("dim", Attribute::Dim),
+    ("italic", Attribute::Italic),
+    ("underline", Attribute::Underlined),
+    ("strikethrough", Attribute::CrossedOut),
+    ("reverse", Attribute::Reverse),
+    ("hidden", Attribute::Hidden),
+    ("slow_blink", Attribute::SlowBlink),
+    ("rapid_blink", Attribute::RapidBlink),
+    ("overline", Attribute::OverLined),
+    ("double_underline", Attribute::DoubleUnderlined),
+    ("curly_underline", Attribute::Undercurled),
+    ("dotted_underline", Attribute::Underdotted),
+    ("dashed_underline", Attribute::Underdashed),
+];
+
+// Raises on unknown modifiers, the same as colors, instead of drawing the text unstyled
+fn atom_to_text_attribute(atom: Atom, env: Env) -> NifResult<Attribute> {
+    let name = atom.to_term(env).atom_to_string().unwrap();
+
+    match text_attribute(&name) {
+        Some(attribute) => Ok(attribute),
+        None => Err(Error::RaiseTerm(Box::new((
+            atoms::invalid_text_modifier(),
+            format!(":{}", name),
+        )))),
     }
 }
 
-fn atom_to_color(atom: Atom, env: Env) -> Color {
-    match atom.to_term(env).atom_to_string().unwrap().as_str() {
-        "white" => Color::White,
-        "black" => Color::Black,
-        "grey" => Color::Grey,
-        "dark_grey" => Color::DarkGrey,
-        "red" => Color::Red,
-        "dark_red" => Color::DarkRed,
-        "green" => Color::Green,
-        "dark_green" => Color::DarkGreen,
-        "yellow" => Color::Yellow,
-        "dark_yellow" => Color::DarkYellow,
-        "blue" => Color::Blue,
-        "dark_blue" => Color::DarkBlue,
-        "magenta" => Color::Magenta,
-        "dark_magenta" => Color::DarkMagenta,
-        "cyan" => Color::Cyan,
-        "dark_cyan" => Color::DarkCyan,
-        _ => Color::Reset,
-    }
+fn text_attribute(name: &str) -> Option<Attribute> {
+    TEXT_ATTRIBUTES
+        .iter()
+        .find(|(attribute_name, _)| *attribute_name == name)
+        .map(|(_, attribute)| *attribute)
 }
 
-fn atom_to_text_attribute(atom: Atom, env: Env) -> Attribute {
-    match atom.to_term(env).atom_to_string().unwrap().as_str() {
-        "bold" => Attribute::Bold,
-        "dim" => Attribute::Dim,
-        "italic" => Attribute::Italic,
-        "underline" => Attribute::Underlined,
-        "strikethrough" => Attribute::CrossedOut,
-        _ => Attribute::Reset,
-    }
+fn text_attributes_to_atoms(attributes: Attributes, env: Env) -> Vec<Atom> {
+    TEXT_ATTRIBUTES
+        .iter()
+        .filter(|(_, attribute)| attributes.has(*attribute))
+        .map(|(name, _)| Atom::from_str(env, name).unwrap())
+        .collect()
 }
 
 #[derive(Debug, NifStruct)]
@@ -126,6 +244,8 @@ fn atom_to_text_attribute(atom: Atom, env: Env) -> Attribute {
 struct KeyEvent<T: Encoder> {
     code: T,
     modifiers: Vec<Atom>,
+    kind: Atom,
+    state: Vec<Atom>,
 }
 
 #[derive(Debug, NifStruct)]
@@ -135,63 +255,302 @@ struct ResizeEvent {
     height: u16,
 }
 
+#[derive(Debug, NifStruct)]
+#[module = "Orange.Terminal.MouseEvent"]
+struct MouseEvent {
+    kind: Atom,
+    button: Option<Atom>,
+    column: u16,
+    row: u16,
+    modifiers: Vec<Atom>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Orange.Terminal.PasteEvent"]
+struct PasteEvent {
+    content: String,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Orange.Terminal.FocusEvent"]
+struct FocusEvent {
+    focused: bool,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Orange.Terminal.ResumeEvent"]
+struct ResumeEvent {}
+
+#[derive(Debug, NifStruct)]
+#[module = "Orange.Terminal.ClipboardEvent"]
+struct ClipboardEvent {
+    target: ClipboardTarget,
+    content: String,
+}
+
+// Waits at most `timeout_ms` for an event. Returns {:ok, event}, or :timeout if no event
+// arrives in time.
 #[rustler::nif(schedule = "DirtyIo")]
-fn poll_event(env: Env) -> Term {
-    match event::read().unwrap() {
-        Event::Key(event) => {
-            let code = format_key_code(event.code, env);
-            let modifiers = format_key_modifiers(event.modifiers, env);
-
-            if let Some(code) = code {
-                let key_event = KeyEvent { code, modifiers };
-                key_event.encode(env)
-            } else {
-                poll_event(env)
+fn poll_event(env: Env, timeout_ms: u64) -> NifResult<Term> {
+    output::check_input().map_err(io_error)?;
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+
+    if guard::take_resumed() {
+        return Ok((atoms::ok(), ResumeEvent {}).encode(env));
+    }
+
+    match next_event(deadline, event::poll, read_event).map_err(io_error)? {
+        Some(event) => Ok((atoms::ok(), format_event(event, env).unwrap()).encode(env)),
+        None => Ok(atoms::timeout().encode(env)),
+    }
+}
+
+// Returns at most `max` events which are already buffered, without blocking.
+#[rustler::nif(schedule = "DirtyIo")]
+fn drain_events(env: Env, max: usize) -> NifResult<(Atom, Vec<Term>)> {
+    output::check_input().map_err(io_error)?;
+    let mut events = Vec::new();
+
+    if guard::take_resumed() {
+        events.push(ResumeEvent {}.encode(env));
+    }
+
+    let buffered = buffered_events(max.saturating_sub(events.len()), event::poll, read_event)
+        .map_err(io_error)?;
+
+    for event in buffered {
+        events.push(format_event(event, env).unwrap());
+    }
+
+    Ok((atoms::ok(), events))
+}
+
+// Waits until `deadline` for a supported event with `poll`, and reads it with `read`.
+// Returns None if none arrives in time.
+fn next_event(
+    deadline: Instant,
+    mut poll: impl FnMut(Duration) -> std::io::Result<bool>,
+    mut read: impl FnMut() -> std::io::Result<Event>,
+) -> std::io::Result<Option<Event>> {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if !poll(remaining)? {
+            return Ok(None);
+        }
+
+        // Skip events we don't support, e.g. unknown key codes
+        let event = read()?;
+
+        if is_supported_event(&event) {
+            return Ok(Some(event));
+        }
+    }
+}
+
+// Returns at most `max` supported events which `poll` reports as ready without waiting
+fn buffered_events(
+    max: usize,
+    mut poll: impl FnMut(Duration) -> std::io::Result<bool>,
+    mut read: impl FnMut() -> std::io::Result<Event>,
+) -> std::io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+
+    while events.len() < max && poll(Duration::ZERO)? {
+        let event = read()?;
+
+        if is_supported_event(&event) {
+            events.push(event);
+        }
+    }
+
+    Ok(events)
+}
+
+struct EventReader {
+    stopped: Arc<AtomicBool>,
+}
+
+// The reader is stopped when the handle is garbage collected
+impl Drop for EventReader {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+const EVENT_READER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const EVENT_READER_QUERY_WAIT: Duration = Duration::from_millis(10);
+
+// Starts a native thread which reads terminal events and sends them to `pid` as
+// {:event, event} messages. The thread exits when the reader is stopped or `pid` is dead.
+// If reading fails, {:event_reader_error, reason} is sent before exiting.
+#[rustler::nif]
+fn start_event_reader(pid: LocalPid) -> ResourceArc<EventReader> {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let reader = ResourceArc::new(EventReader {
+        stopped: stopped.clone(),
+    });
+
+    thread::spawn(move || {
+        let mut env = OwnedEnv::new();
+
+        while !stopped.load(Ordering::Relaxed) {
+            if guard::take_resumed()
+                && env
+                    .send_and_clear(&pid, |_env| (atoms::event(), ResumeEvent {}))
+                    .is_err()
+            {
+                break;
+            }
+
+            let poll =
+                || output::check_input().and_then(|_| event::poll(EVENT_READER_POLL_INTERVAL));
+
+            let event = query::with_input(|| match poll() {
+                Ok(false) => None,
+                Ok(true) => Some(read_event()),
+                Err(err) => Some(Err(err)),
+            });
+
+            let event = match event {
+                Some(Some(event)) => event,
+                Some(None) => continue,
+                // A query is reading the terminal input
+                None => {
+                    thread::sleep(EVENT_READER_QUERY_WAIT);
+                    continue;
+                }
+            };
+
+            let result = match event {
+                Ok(event) if !is_supported_event(&event) => continue,
+                Ok(event) => env.send_and_clear(&pid, |env| {
+                    (atoms::event(), format_event(event, env).unwrap())
+                }),
+                Err(err) => {
+                    let reason = ErrorReason::from(&err);
+                    let _ = env.send_and_clear(&pid, |_env| (atoms::event_reader_error(), reason));
+                    break;
+                }
+            };
+
+            if result.is_err() {
+                break;
             }
         }
+    });
+
+    reader
+}
+
+#[rustler::nif]
+fn stop_event_reader(reader: ResourceArc<EventReader>) -> Atom {
+    reader.stopped.store(true, Ordering::Relaxed);
+    atoms::ok()
+}
+
+// Reads an event and records it if a recording is active
+fn read_event() -> std::io::Result<Event> {
+    event::read().inspect(recording::record_event)
+}
+
+fn format_event(event: Event, env: Env) -> Option<Term> {
+    let term = match event {
+        Event::Key(event) => {
+            let key_event = KeyEvent {
+                code: format_key_code(event.code, env)?,
+                modifiers: format_key_modifiers(event.modifiers, env),
+                kind: format_key_event_kind(event.kind, env),
+                state: format_key_event_state(event.state, env),
+            };
+            key_event.encode(env)
+        }
 
         Event::Resize(width, height) => {
             let resize_event = ResizeEvent { width, height };
             resize_event.encode(env)
         }
 
-        _ => poll_event(env),
+        Event::Mouse(event) => {
+            let (kind, button) = format_mouse_event_kind(event.kind, env);
+            let mouse_event = MouseEvent {
+                kind,
+                button,
+                column: event.column,
+                row: event.row,
+                modifiers: format_key_modifiers(event.modifiers, env),
+            };
+            mouse_event.encode(env)
+        }
+
+        Event::Paste(content) => {
+            let paste_event = PasteEvent { content };
+            paste_event.encode(env)
+        }
+
+        Event::FocusGained | Event::FocusLost => focus_event(&event)?.encode(env),
+    };
+
+    Some(term)
+}
+
+fn focus_event(event: &Event) -> Option<FocusEvent> {
+    match event {
+        Event::FocusGained => Some(FocusEvent { focused: true }),
+        Event::FocusLost => Some(FocusEvent { focused: false }),
+        _ => None,
     }
 }
 
 fn format_key_code(code: KeyCode, env: Env) -> Option<Term> {
-    let atom = |string: &str| {
-        let atom = Atom::from_str(env, string).unwrap();
-        Some(atom.to_term(env))
-    };
+    let atom = Atom::from_str(env, &key_code_name(code)?).unwrap();
 
     match code {
-        KeyCode::Backspace => atom("backspace"),
-        KeyCode::Enter => atom("enter"),
-        KeyCode::Left => atom("left"),
-        KeyCode::Right => atom("right"),
-        KeyCode::Up => atom("up"),
-        KeyCode::Down => atom("down"),
-        KeyCode::Home => atom("home"),
-        KeyCode::End => atom("end"),
-        KeyCode::PageUp => atom("page_up"),
-        KeyCode::PageDown => atom("page_down"),
-        KeyCode::Tab => atom("tab"),
-        KeyCode::BackTab => atom("back_tab"),
-        KeyCode::Delete => atom("delete"),
-        KeyCode::Insert => atom("insert"),
-        KeyCode::F(n) => atom(format!("f{}", n).as_str()),
-        KeyCode::Char(c) => Some((atom("char"), c.to_string()).encode(env)),
-        KeyCode::Null => atom("null"),
-        KeyCode::Esc => atom("esc"),
-        KeyCode::CapsLock => atom("caps_lock"),
-        KeyCode::ScrollLock => atom("scroll_lock"),
-        KeyCode::NumLock => atom("num_lock"),
-        KeyCode::PrintScreen => atom("print_screen"),
-        KeyCode::Pause => atom("pause"),
-        KeyCode::Menu => atom("menu"),
-        KeyCode::KeypadBegin => atom("keypad_begin"),
-        _ => None,
+        KeyCode::Char(c) => Some((atom, c.to_string()).encode(env)),
+        _ => Some(atom.encode(env)),
+    }
+}
+
+// The atom of the key code, or None if the key is not supported. Characters are encoded
+// as {:char, c}.
+fn key_code_name(code: KeyCode) -> Option<Cow<'static, str>> {
+    let name = match code {
+        KeyCode::Backspace => "backspace",
+        KeyCode::Enter => "enter",
+        KeyCode::Left => "left",
+        KeyCode::Right => "right",
+        KeyCode::Up => "up",
+        KeyCode::Down => "down",
+        KeyCode::Home => "home",
+        KeyCode::End => "end",
+        KeyCode::PageUp => "page_up",
+        KeyCode::PageDown => "page_down",
+        KeyCode::Tab => "tab",
+        KeyCode::BackTab => "back_tab",
+        KeyCode::Delete => "delete",
+        KeyCode::Insert => "insert",
+        KeyCode::F(n) => return Some(Cow::Owned(format!("f{}", n))),
+        KeyCode::Char(_) => "char",
+        KeyCode::Null => "null",
+        KeyCode::Esc => "esc",
+        KeyCode::CapsLock => "caps_lock",
+        KeyCode::ScrollLock => "scroll_lock",
+        KeyCode::NumLock => "num_lock",
+        KeyCode::PrintScreen => "print_screen",
+        KeyCode::Pause => "pause",
+        KeyCode::Menu => "menu",
+        KeyCode::KeypadBegin => "keypad_begin",
+        KeyCode::Media(_) | KeyCode::Modifier(_) => return None,
+    };
+
+    Some(Cow::Borrowed(name))
+}
+
+// Whether format_event/2 can encode the event
+fn is_supported_event(event: &Event) -> bool {
+    match event {
+        Event::Key(event) => key_code_name(event.code).is_some(),
+        _ => true,
     }
 }
 
@@ -222,61 +581,880 @@ fn format_key_modifiers(modifiers: KeyModifiers, env: Env) -> Vec<Atom> {
     result
 }
 
+fn format_key_event_kind(kind: KeyEventKind, env: Env) -> Atom {
+    Atom::from_str(env, key_event_kind_name(kind)).unwrap()
+}
+
+fn key_event_kind_name(kind: KeyEventKind) -> &'static str {
+    match kind {
+        KeyEventKind::Press => "press",
+        KeyEventKind::Repeat => "repeat",
+        KeyEventKind::Release => "release",
+    }
+}
+
+fn format_key_event_state(state: KeyEventState, env: Env) -> Vec<Atom> {
+    key_event_state_names(state)
+        .into_iter()
+        .map(|name| Atom::from_str(env, name).unwrap())
+        .collect()
+}
+
+fn key_event_state_names(state: KeyEventState) -> Vec<&'static str> {
+    let mut result = Vec::new();
+
+    if state.contains(KeyEventState::KEYPAD) {
+        result.push("keypad");
+    }
+
+    // crossterm uses the same bit for both caps lock and num lock, so we can't tell which
+    // one is on
+    if state.contains(KeyEventState::CAPS_LOCK) {
+        result.push("caps_or_num_lock");
+    }
+
+    result
+}
+
+fn format_mouse_event_kind(kind: MouseEventKind, env: Env) -> (Atom, Option<Atom>) {
+    let atom = |string: &str| Atom::from_str(env, string).unwrap();
+
+    let button = |button: MouseButton| match button {
+        MouseButton::Left => atom("left"),
+        MouseButton::Right => atom("right"),
+        MouseButton::Middle => atom("middle"),
+    };
+
+    match kind {
+        MouseEventKind::Down(b) => (atom("down"), Some(button(b))),
+        MouseEventKind::Up(b) => (atom("up"), Some(button(b))),
+        MouseEventKind::Drag(b) => (atom("drag"), Some(button(b))),
+        MouseEventKind::Moved => (atom("moved"), None),
+        MouseEventKind::ScrollUp => (atom("scroll_up"), None),
+        MouseEventKind::ScrollDown => (atom("scroll_down"), None),
+        MouseEventKind::ScrollLeft => (atom("scroll_left"), None),
+        MouseEventKind::ScrollRight => (atom("scroll_right"), None),
+    }
+}
+
+#[rustler::nif]
+fn enter_alternate_screen() -> NifResult<Atom> {
+    render::reset_cursor_position();
+    ok_result(
+        execute!(output::writer(), EnterAlternateScreen)
+            .inspect(|_| guard::enable(Mode::AlternateScreen)),
+    )
+}
+
+#[rustler::nif]
+fn leave_alternate_screen() -> NifResult<Atom> {
+    render::reset_cursor_position();
+    ok_result(
+        execute!(output::writer(), LeaveAlternateScreen)
+            .inspect(|_| guard::disable(Mode::AlternateScreen)),
+    )
+}
+
+#[rustler::nif]
+fn enable_raw_mode() -> NifResult<Atom> {
+    if let Some(terminal) = output::virtual_terminal() {
+        terminal.set_raw_mode(true);
+        return Ok(atoms::ok());
+    }
+
+    if let Err(err) = output::check_input() {
+        return ok_result(Err(err));
+    }
+
+    guard::save_termios();
+    ok_result(terminal::enable_raw_mode().inspect(|_| guard::enable(Mode::Raw)))
+}
+
+#[rustler::nif]
+fn disable_raw_mode() -> NifResult<Atom> {
+    if let Some(terminal) = output::virtual_terminal() {
+        terminal.set_raw_mode(false);
+        return Ok(atoms::ok());
+    }
+
+    ok_result(terminal::disable_raw_mode().inspect(|_| guard::disable(Mode::Raw)))
+}
+
+#[rustler::nif]
+fn show_cursor() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), cursor::Show).inspect(|_| guard::disable(Mode::HiddenCursor)),
+    )
+}
+
+#[rustler::nif]
+fn hide_cursor() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), cursor::Hide).inspect(|_| guard::enable(Mode::HiddenCursor)),
+    )
+}
+
+// Moves the cursor relative to the viewport. Draws move it back there when they are done.
+#[rustler::nif]
+fn move_cursor(x: u16, y: u16) -> NifResult<Atom> {
+    render::set_cursor_position(x as usize, y as usize);
+    ok_result(execute!(
+        output::writer(),
+        cursor::MoveTo(x, viewport::origin().saturating_add(y))
+    ))
+}
+
+// The discriminants are the DECSCUSR parameters
+#[derive(Clone, Copy, NifUnitEnum)]
+enum CursorShape {
+    Default = 0,
+    BlinkingBlock = 1,
+    SteadyBlock = 2,
+    BlinkingUnderline = 3,
+    SteadyUnderline = 4,
+    BlinkingBar = 5,
+    SteadyBar = 6,
+}
+
+impl CursorShape {
+    fn from_parameter(parameter: u8) -> Self {
+        match parameter {
+            1 => CursorShape::BlinkingBlock,
+            2 => CursorShape::SteadyBlock,
+            3 => CursorShape::BlinkingUnderline,
+            4 => CursorShape::SteadyUnderline,
+            5 => CursorShape::BlinkingBar,
+            6 => CursorShape::SteadyBar,
+            _ => CursorShape::Default,
+        }
+    }
+}
+
+#[rustler::nif]
+fn set_cursor_shape(shape: CursorShape) -> NifResult<Atom> {
+    let parameter = shape as u8;
+    let style = match shape {
+        CursorShape::Default => cursor::SetCursorStyle::DefaultUserShape,
+        CursorShape::BlinkingBlock => cursor::SetCursorStyle::BlinkingBlock,
+        CursorShape::SteadyBlock => cursor::SetCursorStyle::SteadyBlock,
+        CursorShape::BlinkingUnderline => cursor::SetCursorStyle::BlinkingUnderScore,
+        CursorShape::SteadyUnderline => cursor::SetCursorStyle::SteadyUnderScore,
+        CursorShape::BlinkingBar => cursor::SetCursorStyle::BlinkingBar,
+        CursorShape::SteadyBar => cursor::SetCursorStyle::SteadyBar,
+    };
+
+    ok_result(execute!(output::writer(), style).inspect(|_| guard::set_cursor_shape(parameter)))
+}
+
+#[rustler::nif]
+fn save_cursor_position() -> NifResult<Atom> {
+    render::save_cursor_position();
+    ok_result(execute!(output::writer(), cursor::SavePosition))
+}
+
+#[rustler::nif]
+fn restore_cursor_position() -> NifResult<Atom> {
+    render::restore_cursor_position();
+    ok_result(execute!(output::writer(), cursor::RestorePosition))
+}
+
+// Sets both the window title and the icon name. Control characters would end the sequence
+// early, so they are dropped.
+#[rustler::nif]
+fn set_title(title: String) -> NifResult<Atom> {
+    let title = title
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    ok_result(execute!(output::writer(), SetTitle(&title)).inspect(|_| guard::set_title(title)))
+}
+
+// Saves the window title and icon name on the terminal title stack (XTWINOPS 22)
+#[rustler::nif]
+fn push_title() -> NifResult<Atom> {
+    ok_result(execute!(output::writer(), Print("\x1b[22;0t")).inspect(|_| guard::push_title()))
+}
+
+// Restores the window title and icon name saved by push_title (XTWINOPS 23)
+#[rustler::nif]
+fn pop_title() -> NifResult<Atom> {
+    ok_result(execute!(output::writer(), Print("\x1b[23;0t")).inspect(|_| guard::pop_title()))
+}
+
+#[rustler::nif]
+fn enable_mouse_capture() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), EnableMouseCapture)
+            .inspect(|_| guard::enable(Mode::MouseCapture)),
+    )
+}
+
+#[rustler::nif]
+fn disable_mouse_capture() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), DisableMouseCapture)
+            .inspect(|_| guard::disable(Mode::MouseCapture)),
+    )
+}
+
+#[rustler::nif]
+fn enable_bracketed_paste() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), EnableBracketedPaste)
+            .inspect(|_| guard::enable(Mode::BracketedPaste)),
+    )
+}
+
+#[rustler::nif]
+fn disable_bracketed_paste() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), DisableBracketedPaste)
+            .inspect(|_| guard::disable(Mode::BracketedPaste)),
+    )
+}
+
+#[rustler::nif]
+fn enable_focus_reporting() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), EnableFocusChange)
+            .inspect(|_| guard::enable(Mode::FocusReporting)),
+    )
+}
+
+#[rustler::nif]
+fn disable_focus_reporting() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), DisableFocusChange)
+            .inspect(|_| guard::disable(Mode::FocusReporting)),
+    )
+}
+
+#[derive(Clone, Copy, NifUnitEnum)]
+enum KeyboardEnhancementFlag {
+    DisambiguateEscapeCodes,
+    ReportEventTypes,
+    ReportAlternateKeys,
+    ReportAllKeysAsEscapeCodes,
+}
+
+impl KeyboardEnhancementFlag {
+    const ALL: [KeyboardEnhancementFlag; 4] = [
+        KeyboardEnhancementFlag::DisambiguateEscapeCodes,
+        KeyboardEnhancementFlag::ReportEventTypes,
+        KeyboardEnhancementFlag::ReportAlternateKeys,
+        KeyboardEnhancementFlag::ReportAllKeysAsEscapeCodes,
+    ];
+
+    fn flag(self) -> KeyboardEnhancementFlags {
+        match self {
+            KeyboardEnhancementFlag::DisambiguateEscapeCodes => {
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+            }
+            KeyboardEnhancementFlag::ReportEventTypes => {
+                KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+            }
+            KeyboardEnhancementFlag::ReportAlternateKeys => {
+                KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS
+            }
+            KeyboardEnhancementFlag::ReportAllKeysAsEscapeCodes => {
+                KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+            }
+        }
+    }
+
+    fn from_bits(flags: KeyboardEnhancementFlags) -> Vec<Self> {
+        Self::ALL
+            .into_iter()
+            .filter(|flag| flags.contains(flag.flag()))
+            .collect()
+    }
+}
+
+#[rustler::nif]
+fn push_keyboard_enhancement_flags(flags: Vec<KeyboardEnhancementFlag>) -> NifResult<Atom> {
+    let flags = flags
+        .iter()
+        .fold(KeyboardEnhancementFlags::empty(), |acc, flag| {
+            acc | flag.flag()
+        });
+
+    ok_result(
+        execute!(output::writer(), PushKeyboardEnhancementFlags(flags))
+            .inspect(|_| guard::push_keyboard_enhancement(flags)),
+    )
+}
+
+#[rustler::nif]
+fn pop_keyboard_enhancement_flags() -> NifResult<Atom> {
+    ok_result(
+        execute!(output::writer(), PopKeyboardEnhancementFlags)
+            .inspect(|_| guard::pop_keyboard_enhancement()),
+    )
+}
+
+const TERMINAL_QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Asks the terminal for the current keyboard enhancement flags. Terminals which don't
+// support the kitty keyboard protocol only answer the device attributes request. The probe
+// reads the reply from the terminal, so it must be called before event polling starts,
+// otherwise the reply is consumed as key events.
+#[rustler::nif(schedule = "DirtyIo")]
+fn supports_keyboard_enhancement() -> NifResult<(Atom, bool)> {
+    let request = format!("\x1b[?u{}", query::DEVICE_ATTRIBUTES_REQUEST);
+    let reply = query::query(
+        &request,
+        TERMINAL_QUERY_TIMEOUT,
+        query::has_device_attributes,
+    )
+    .map_err(io_error)?;
+
+    Ok((atoms::ok(), query::has_keyboard_enhancement_flags(&reply)))
+}
+
+// Asks the terminal for the state of DEC mode 2026 with DECRQM. Terminals which don't
+// know DECRQM ignore it, so the request is followed by a device attributes request which
+// every terminal answers. Same as supports_keyboard_enhancement, it must be called before
+// event polling starts.
+#[rustler::nif(schedule = "DirtyIo")]
+fn supports_synchronized_output() -> NifResult<(Atom, bool)> {
+    let request = format!("\x1b[?2026$p{}", query::DEVICE_ATTRIBUTES_REQUEST);
+    let reply = query::query(
+        &request,
+        TERMINAL_QUERY_TIMEOUT,
+        query::has_device_attributes,
+    )
+    .map_err(io_error)?;
+
+    Ok((atoms::ok(), query::has_synchronized_output_mode(&reply)))
+}
+
+type RgbColor = (Atom, u8, u8, u8);
+
+#[derive(NifMap)]
+struct TerminalColors {
+    foreground: Option<RgbColor>,
+    background: Option<RgbColor>,
+}
+
+// Asks the terminal for its default colors with OSC 10 and OSC 11. Colors which the
+// terminal doesn't report within `timeout_ms` are nil. Same as supports_keyboard_enhancement,
+// it must be called before event polling starts.
+#[rustler::nif(schedule = "DirtyIo")]
+fn query_terminal_colors(timeout_ms: u64) -> NifResult<(Atom, TerminalColors)> {
+    let request = format!(
+        "\x1b]10;?\x1b\\\x1b]11;?\x1b\\{}",
+        query::DEVICE_ATTRIBUTES_REQUEST
+    );
+    let reply = query::query(
+        &request,
+        Duration::from_millis(timeout_ms),
+        query::has_device_attributes,
+    )
+    .map_err(io_error)?;
+
+    let mut colors = TerminalColors {
+        foreground: None,
+        background: None,
+    };
+
+    // The replies are OSC 10 ; spec and OSC 11 ; spec
+    for sequence in query::osc_sequences(&reply) {
+        let Some((code, spec)) = sequence.split_once(';') else {
+            continue;
+        };
+
+        let color = color::parse_color_spec(spec).map(|(r, g, b)| (atoms::rgb(), r, g, b));
+
+        match code {
+            "10" => colors.foreground = color,
+            "11" => colors.background = color,
+            _ => (),
+        }
+    }
+
+    Ok((atoms::ok(), colors))
+}
+
+#[rustler::nif]
+fn set_synchronized_output(enabled: bool) -> Atom {
+    render::set_synchronized_output(enabled);
+
+    match enabled {
+        true => guard::enable(Mode::SynchronizedOutput),
+        false => guard::disable(Mode::SynchronizedOutput),
+    }
+
+    atoms::ok()
+}
+
+#[rustler::nif]
+fn copy_to_clipboard(text: String, target: ClipboardTarget) -> NifResult<Atom> {
+    ok_result(clipboard::copy(&mut output::writer(), &text, target))
+}
+
+// Reads the clipboard in a native thread, since the terminal may ask the user first. The
+// content is sent to `pid` as {:event, %ClipboardEvent{}}. Nothing is sent if the terminal
+// doesn't reply or the reply can't be read.
+#[rustler::nif]
+fn request_clipboard(pid: LocalPid, target: ClipboardTarget) -> Atom {
+    thread::spawn(move || {
+        if let Ok(Some(content)) = clipboard::read(target) {
+            let _ = OwnedEnv::new().send_and_clear(&pid, |env| {
+                let clipboard_event = ClipboardEvent { target, content };
+                (atoms::event(), clipboard_event.encode(env))
+            });
+        }
+    });
+
+    atoms::ok()
+}
+
+// Selects where the terminal output is written: :stdout, :stderr, :tty or {:fd, fd}
+#[rustler::nif]
+fn set_output(target: OutputTarget) -> NifResult<Atom> {
+    ok_result(output::set_output(target))
+}
+
+// Clears the inline viewport, or the whole screen if there is none
+#[rustler::nif]
+fn clear() -> NifResult<Atom> {
+    ok_result(viewport::clear(&mut output::writer()))
+}
+
+// Reserves `height` lines under the cursor and draws the next frames in them instead of
+// the whole screen. Returns {:ok, height}, where height is capped to the terminal height.
+// The cursor position is read from the terminal, so it must be called before event
+// polling starts.
+#[rustler::nif(schedule = "DirtyIo")]
+fn enter_inline_viewport(height: u16) -> NifResult<(Atom, u16)> {
+    render::reset_cursor_position();
+    value_result(viewport::enter(&mut output::writer(), height))
+}
+
+// Moves the cursor below the inline viewport, leaving the last frame on the screen
+#[rustler::nif]
+fn leave_inline_viewport() -> NifResult<Atom> {
+    render::reset_cursor_position();
+    ok_result(viewport::leave(&mut output::writer()))
+}
+
+// Restores the terminal and stops the process with SIGTSTP, like Ctrl+Z does in cooked
+// mode. Returns after the process is continued, e.g. with `fg`, once the modes are enabled
+// again. The next polled event is then a %ResumeEvent{}, since the screen must be redrawn.
+#[rustler::nif(schedule = "DirtyIo")]
+fn suspend() -> NifResult<Atom> {
+    ok_result(guard::suspend())
+}
+
+// Undoes every mode enabled by the binding: raw mode, alternate screen, inline viewport,
+// hidden cursor, cursor shape, input modes, keyboard enhancement flags and pushed titles.
+// The same is done when the library is unloaded, the process exits or the VM is killed by
+// a fatal signal.
 #[rustler::nif]
-fn enter_alternate_screen() {
-    execute!(io::stdout(), EnterAlternateScreen).unwrap();
+fn restore_terminal() -> NifResult<Atom> {
+    render::reset_cursor_position();
+    ok_result(guard::restore())
 }
 
+// Starts recording the terminal output and resizes to an asciicast v2 file at `path`. The
+// input is also recorded if `input` is true. The header has the current terminal size.
 #[rustler::nif]
-fn leave_alternate_screen() {
-    execute!(io::stdout(), LeaveAlternateScreen).unwrap();
+fn start_recording(path: String, input: bool) -> NifResult<Atom> {
+    ok_result(
+        output_size().and_then(|(width, height)| recording::start(&path, width, height, input)),
+    )
 }
 
 #[rustler::nif]
-fn enable_raw_mode() {
-    terminal::enable_raw_mode().unwrap();
+fn stop_recording() -> NifResult<Atom> {
+    ok_result(recording::stop())
 }
 
 #[rustler::nif]
-fn disable_raw_mode() {
-    terminal::disable_raw_mode().unwrap();
+fn new_virtual_terminal(width: u16, height: u16) -> ResourceArc<VirtualTerminal> {
+    ResourceArc::new(VirtualTerminal::new(width as usize, height as usize))
 }
 
+// Feeds bytes to the virtual terminal, as if they were written to its output
 #[rustler::nif]
-fn show_cursor() {
-    execute!(io::stdout(), cursor::Show).unwrap();
+fn virtual_terminal_write(terminal: ResourceArc<VirtualTerminal>, bytes: Binary) -> Atom {
+    terminal.write(bytes.as_slice());
+    atoms::ok()
 }
 
 #[rustler::nif]
-fn hide_cursor() {
-    execute!(io::stdout(), cursor::Hide).unwrap();
+fn virtual_terminal_resize(
+    terminal: ResourceArc<VirtualTerminal>,
+    width: u16,
+    height: u16,
+) -> Atom {
+    terminal.resize(width as usize, height as usize);
+    atoms::ok()
+}
+
+#[derive(NifStruct)]
+#[module = "Orange.Terminal.VirtualTerminal.Cell"]
+struct VirtualCell<'a> {
+    character: String,
+    foreground: Option<Term<'a>>,
+    background: Option<Term<'a>>,
+    underline_color: Option<Term<'a>>,
+    modifiers: Vec<Atom>,
+    hyperlink: Option<String>,
 }
 
+#[derive(NifStruct)]
+#[module = "Orange.Terminal.VirtualTerminal.Screen"]
+struct VirtualScreen<'a> {
+    width: usize,
+    height: usize,
+    rows: Vec<Vec<VirtualCell<'a>>>,
+    cursor: (usize, usize),
+    cursor_visible: bool,
+    cursor_shape: CursorShape,
+    alternate_screen: bool,
+    raw_mode: bool,
+    bracketed_paste: bool,
+    mouse_capture: bool,
+    focus_reporting: bool,
+    synchronized_output: bool,
+    keyboard_enhancement_flags: Vec<KeyboardEnhancementFlag>,
+    title: String,
+}
+
+// Returns what the virtual terminal displays, and its modes
 #[rustler::nif]
-fn clear() {
-    let mut writer = io::stdout();
-    queue_command(&mut writer, terminal::Clear(terminal::ClearType::All));
-    queue_command(&mut writer, terminal::Clear(terminal::ClearType::Purge));
-    flush(&mut writer);
+fn virtual_terminal_screen(env: Env, terminal: ResourceArc<VirtualTerminal>) -> VirtualScreen {
+    let screen = terminal.screen();
+    let encode_color = |color: Option<Color>| color.map(|color| color::encode_color(color, env));
+
+    let rows = screen
+        .rows()
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| VirtualCell {
+                    character: cell.character.clone(),
+                    foreground: encode_color(cell.style.foreground_color),
+                    background: encode_color(cell.style.background_color),
+                    underline_color: encode_color(cell.style.underline_color),
+                    modifiers: text_attributes_to_atoms(cell.style.attributes, env),
+                    hyperlink: cell.hyperlink.clone(),
+                })
+                .collect()
+        })
+        .collect();
+
+    // The flags which are in effect, at the top of the stack
+    let keyboard_enhancement_flags =
+        screen
+            .keyboard_enhancement_flags
+            .last()
+            .map_or_else(Vec::new, |bits| {
+                KeyboardEnhancementFlag::from_bits(KeyboardEnhancementFlags::from_bits_truncate(
+                    *bits,
+                ))
+            });
+
+    VirtualScreen {
+        width: screen.width,
+        height: screen.height,
+        rows,
+        cursor: screen.cursor,
+        cursor_visible: screen.cursor_visible,
+        cursor_shape: CursorShape::from_parameter(screen.cursor_shape),
+        alternate_screen: screen.alternate_screen,
+        raw_mode: screen.raw_mode,
+        bracketed_paste: screen.bracketed_paste,
+        mouse_capture: screen.mouse_capture,
+        focus_reporting: screen.focus_reporting,
+        synchronized_output: screen.synchronized_output,
+        keyboard_enhancement_flags,
+        title: screen.title.clone(),
+    }
 }
 
 #[rustler::nif]
-fn terminal_size(env: Env) -> Term {
-    terminal::size().unwrap().encode(env)
+fn terminal_size() -> NifResult<(Atom, (u16, u16))> {
+    value_result(output_size())
+}
+
+// The size of the terminal the output is written to
+fn output_size() -> std::io::Result<(u16, u16)> {
+    match output::virtual_terminal() {
+        Some(terminal) => {
+            let (width, height) = terminal.size();
+            Ok((width as u16, height as u16))
+        }
+        None => output::check_input().and_then(|_| terminal::size()),
+    }
+}
+
+#[rustler::nif]
+fn color_depth() -> Atom {
+    color::color_depth().to_atom()
 }
 
 rustler::init!(
     "Elixir.Orange.Terminal.Binding",
     [
         draw,
+        render_to_binary,
+        new_frame_buffer,
+        present,
+        invalidate,
         enter_alternate_screen,
         leave_alternate_screen,
         enable_raw_mode,
         disable_raw_mode,
         show_cursor,
         hide_cursor,
+        move_cursor,
+        set_cursor_shape,
+        save_cursor_position,
+        restore_cursor_position,
+        set_title,
+        push_title,
+        pop_title,
+        enable_mouse_capture,
+        disable_mouse_capture,
+        enable_bracketed_paste,
+        disable_bracketed_paste,
+        enable_focus_reporting,
+        disable_focus_reporting,
+        push_keyboard_enhancement_flags,
+        pop_keyboard_enhancement_flags,
+        supports_keyboard_enhancement,
+        supports_synchronized_output,
+        set_synchronized_output,
+        query_terminal_colors,
+        copy_to_clipboard,
+        request_clipboard,
+        set_output,
         clear,
+        enter_inline_viewport,
+        leave_inline_viewport,
+        restore_terminal,
+        start_recording,
+        stop_recording,
+        new_virtual_terminal,
+        virtual_terminal_write,
+        virtual_terminal_resize,
+        virtual_terminal_screen,
+        suspend,
         poll_event,
+        drain_events,
+        start_event_reader,
+        stop_event_reader,
         terminal_size,
-    ]
+        color_depth,
+    ],
+    load = load
 );
+
+// rustler::resource! expands to an impl block inside the function
+#[allow(non_local_definitions)]
+fn load(env: Env, _info: Term) -> bool {
+    rustler::resource!(EventReader, env);
+    rustler::resource!(FrameBuffer, env);
+    rustler::resource!(VirtualTerminal, env);
+    guard::install();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    mod is_supported_event {
+        use super::super::*;
+        use crossterm::event::{MediaKeyCode, ModifierKeyCode};
+
+        #[test]
+        fn unsupported_key_codes() {
+            let key = |code| Event::Key(event::KeyEvent::from(code));
+
+            assert!(is_supported_event(&key(KeyCode::F(5))));
+            assert!(is_supported_event(&key(KeyCode::Char('a'))));
+            assert!(!is_supported_event(&key(KeyCode::Media(
+                MediaKeyCode::Play
+            ))));
+            assert!(!is_supported_event(&key(KeyCode::Modifier(
+                ModifierKeyCode::LeftShift
+            ))));
+            assert!(is_supported_event(&Event::FocusGained));
+        }
+    }
+
+    mod text_attribute {
+        use super::super::*;
+
+        #[test]
+        fn known_modifiers() {
+            assert_eq!(text_attribute("bold"), Some(Attribute::Bold));
+            assert_eq!(
+                text_attribute("curly_underline"),
+                Some(Attribute::Undercurled)
+            );
+        }
+
+        #[test]
+        fn unknown_modifiers() {
+            assert_eq!(text_attribute("blod"), None);
+            assert_eq!(text_attribute("reset"), None);
+        }
+    }
+
+    // An event source which has `events` ready, then waits for the whole timeout
+    mod fake_input {
+        use super::super::*;
+
+        pub struct FakeInput {
+            events: std::collections::VecDeque<Event>,
+        }
+
+        impl FakeInput {
+            pub fn new(events: Vec<Event>) -> Self {
+                FakeInput {
+                    events: events.into(),
+                }
+            }
+
+            pub fn poll(&mut self, timeout: Duration) -> std::io::Result<bool> {
+                if self.events.is_empty() {
+                    thread::sleep(timeout);
+                }
+
+                Ok(!self.events.is_empty())
+            }
+
+            pub fn read(&mut self) -> std::io::Result<Event> {
+                Ok(self.events.pop_front().unwrap())
+            }
+        }
+
+        pub fn key(c: char) -> Event {
+            Event::Key(event::KeyEvent::from(KeyCode::Char(c)))
+        }
+
+        pub fn media_key() -> Event {
+            Event::Key(event::KeyEvent::from(KeyCode::Media(
+                event::MediaKeyCode::Play,
+            )))
+        }
+    }
+
+    mod next_event {
+        use super::super::*;
+        use super::fake_input::{key, media_key, FakeInput};
+        use std::cell::RefCell;
+
+        fn next(input: FakeInput, timeout: Duration) -> Option<Event> {
+            let input = RefCell::new(input);
+            let deadline = Instant::now() + timeout;
+
+            next_event(
+                deadline,
+                |timeout| input.borrow_mut().poll(timeout),
+                || input.borrow_mut().read(),
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn returns_the_next_supported_event() {
+            let input = FakeInput::new(vec![media_key(), key('a'), key('b')]);
+            assert_eq!(next(input, Duration::from_secs(1)), Some(key('a')));
+        }
+
+        #[test]
+        fn times_out_without_events() {
+            let start = Instant::now();
+
+            assert_eq!(
+                next(FakeInput::new(vec![]), Duration::from_millis(20)),
+                None
+            );
+            assert!(start.elapsed() >= Duration::from_millis(20));
+        }
+
+        #[test]
+        fn times_out_after_unsupported_events() {
+            let input = FakeInput::new(vec![media_key(), media_key()]);
+            assert_eq!(next(input, Duration::from_millis(20)), None);
+        }
+    }
+
+    mod buffered_events {
+        use super::super::*;
+        use super::fake_input::{key, media_key, FakeInput};
+        use std::cell::RefCell;
+
+        fn drain(input: &RefCell<FakeInput>, max: usize) -> Vec<Event> {
+            buffered_events(
+                max,
+                |timeout| input.borrow_mut().poll(timeout),
+                || input.borrow_mut().read(),
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn returns_at_most_max_events() {
+            let input = RefCell::new(FakeInput::new(vec![key('a'), key('b'), key('c')]));
+
+            assert_eq!(drain(&input, 2), vec![key('a'), key('b')]);
+            assert_eq!(drain(&input, 2), vec![key('c')]);
+            assert_eq!(drain(&input, 2), vec![]);
+        }
+
+        #[test]
+        fn skips_unsupported_events() {
+            let input = RefCell::new(FakeInput::new(vec![key('a'), media_key(), key('b')]));
+            assert_eq!(drain(&input, 5), vec![key('a'), key('b')]);
+        }
+    }
+
+    mod focus_event {
+        use super::super::*;
+
+        #[test]
+        fn reports_whether_the_terminal_is_focused() {
+            let focused = |event| focus_event(&event).map(|event| event.focused);
+
+            assert_eq!(focused(Event::FocusGained), Some(true));
+            assert_eq!(focused(Event::FocusLost), Some(false));
+            assert_eq!(focused(Event::Resize(80, 24)), None);
+        }
+    }
+
+    mod key_event_kind_name {
+        use super::super::*;
+
+        #[test]
+        fn names_every_kind() {
+            assert_eq!(key_event_kind_name(KeyEventKind::Press), "press");
+            assert_eq!(key_event_kind_name(KeyEventKind::Repeat), "repeat");
+            assert_eq!(key_event_kind_name(KeyEventKind::Release), "release");
+        }
+    }
+
+    mod key_event_state_names {
+        use super::super::*;
+
+        #[test]
+        fn names_set_states() {
+            assert!(key_event_state_names(KeyEventState::NONE).is_empty());
+            assert_eq!(
+                key_event_state_names(KeyEventState::KEYPAD | KeyEventState::CAPS_LOCK),
+                vec!["keypad", "caps_or_num_lock"]
+            );
+        }
+
+        #[test]
+        fn lock_states_are_not_told_apart() {
+            assert_eq!(
+                key_event_state_names(KeyEventState::NUM_LOCK),
+                vec!["caps_or_num_lock"]
+            );
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rustler::{NifTaggedEnum, ResourceArc};
+
+use crate::guard;
+use crate::recording;
+use crate::virtual_terminal::VirtualTerminal;
+
+// Where the terminal output is written. Events are read by crossterm from stdin if it's a
+// TTY, otherwise from /dev/tty, so they come from the same terminal for every target but a
+// file descriptor, which might be another terminal. A virtual terminal receives the output
+// instead of a real terminal.
+#[derive(NifTaggedEnum)]
+pub enum OutputTarget {
+    Stdout,
+    Stderr,
+    Tty,
+    Fd(i32),
+    Virtual(ResourceArc<VirtualTerminal>),
+}
+
+#[derive(Clone)]
+pub enum Output {
+    Stdout,
+    Stderr,
+    File(Arc<File>),
+    Fd(Arc<File>),
+    Virtual(ResourceArc<VirtualTerminal>),
+}
+
+static OUTPUT: Mutex<Output> = Mutex::new(Output::Stdout);
+
+// Whether the output is a file descriptor, see check_input
+static FD_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_output(target: OutputTarget) -> io::Result<()> {
+    let output = match target {
+        OutputTarget::Stdout => Output::Stdout,
+        OutputTarget::Stderr => Output::Stderr,
+        OutputTarget::Tty => Output::File(Arc::new(open_tty()?)),
+        OutputTarget::Fd(fd) => Output::Fd(Arc::new(duplicate_fd(fd)?)),
+        OutputTarget::Virtual(terminal) => Output::Virtual(terminal),
+    };
+
+    guard::set_output_fd(output.raw_fd());
+    FD_OUTPUT.store(matches!(output, Output::Fd(_)), Ordering::Release);
+    *OUTPUT.lock().unwrap() = output;
+    Ok(())
+}
+
+// The virtual terminal, if it's the current output
+pub fn virtual_terminal() -> Option<ResourceArc<VirtualTerminal>> {
+    match &*OUTPUT.lock().unwrap() {
+        Output::Virtual(terminal) => Some(terminal.clone()),
+        _ => None,
+    }
+}
+
+// Raw mode, the terminal size, events and query replies come from the terminal crossterm
+// reads from, which might not be the terminal behind a file descriptor output. They are not
+// supported with such an output, rather than silently using the wrong terminal.
+pub fn check_input() -> io::Result<()> {
+    if FD_OUTPUT.load(Ordering::Acquire) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the terminal input is not read from file descriptor outputs",
+        ));
+    }
+
+    Ok(())
+}
+
+// Returns a writer to the current output. Writes are buffered until flushed.
+pub fn writer() -> BufWriter<Output> {
+    BufWriter::new(OUTPUT.lock().unwrap().clone())
+}
+
+impl Output {
+    // The file descriptor the terminal is restored on from signal handlers
+    #[cfg(unix)]
+    fn raw_fd(&self) -> i32 {
+        use std::os::fd::AsRawFd;
+
+        match self {
+            Output::Stdout => libc::STDOUT_FILENO,
+            Output::Stderr => libc::STDERR_FILENO,
+            Output::File(file) | Output::Fd(file) => file.as_raw_fd(),
+            // Nothing to restore
+            Output::Virtual(_) => -1,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn raw_fd(&self) -> i32 {
+        match self {
+            Output::Stderr => 2,
+            Output::Virtual(_) => -1,
+            _ => 1,
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout => write_recorded(&mut io::stdout(), buf),
+            Output::Stderr => write_recorded(&mut io::stderr(), buf),
+            Output::File(file) | Output::Fd(file) => write_recorded(&mut file.as_ref(), buf),
+            Output::Virtual(terminal) => {
+                terminal.write(buf);
+                recording::record_output(buf);
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout => io::stdout().flush(),
+            Output::Stderr => io::stderr().flush(),
+            Output::File(file) | Output::Fd(file) => file.as_ref().flush(),
+            Output::Virtual(_) => Ok(()),
+        }
+    }
+}
+
+// Written bytes are also recorded when a recording is active
+fn write_recorded(writer: &mut impl Write, buf: &[u8]) -> io::Result<usize> {
+    let written = writer.write(buf)?;
+    recording::record_output(&buf[..written]);
+    Ok(written)
+}
+
+#[cfg(unix)]
+fn open_tty() -> io::Result<File> {
+    File::options().read(true).write(true).open("/dev/tty")
+}
+
+#[cfg(not(unix))]
+fn open_tty() -> io::Result<File> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// The descriptor is duplicated, so that the caller keeps ownership of `fd`
+#[cfg(unix)]
+fn duplicate_fd(fd: i32) -> io::Result<File> {
+    use std::os::fd::FromRawFd;
+
+    match unsafe { libc::dup(fd) } {
+        -1 => Err(io::Error::last_os_error()),
+        duplicate => Ok(unsafe { File::from_raw_fd(duplicate) }),
+    }
+}
+
+#[cfg(not(unix))]
+fn duplicate_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    // The output and the recording are global, so the tests which change them don't run
+    // concurrently
+    static GLOBALS_LOCK: Mutex<()> = Mutex::new(());
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("orange_output_{}_{}", std::process::id(), name))
+    }
+
+    mod check_input {
+        use super::super::*;
+        use super::GLOBALS_LOCK;
+
+        #[test]
+        fn unsupported_with_an_fd_output() {
+            let _lock = GLOBALS_LOCK.lock().unwrap();
+
+            FD_OUTPUT.store(true, Ordering::Release);
+            let result = check_input();
+            FD_OUTPUT.store(false, Ordering::Release);
+
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Unsupported);
+            assert!(check_input().is_ok());
+        }
+    }
+
+    #[cfg(unix)]
+    mod duplicate_fd {
+        use std::os::fd::AsRawFd;
+
+        use super::super::*;
+        use super::temp_path;
+
+        #[test]
+        fn outlives_the_original_fd() {
+            let path = temp_path("fd");
+            let file = File::create(&path).unwrap();
+
+            let mut duplicate = duplicate_fd(file.as_raw_fd()).unwrap();
+            drop(file);
+            duplicate.write_all(b"hello").unwrap();
+
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn invalid_fd() {
+            assert!(duplicate_fd(-1).is_err());
+        }
+    }
+
+    mod write_recorded {
+        use super::super::*;
+        use super::{temp_path, GLOBALS_LOCK};
+
+        #[test]
+        fn records_the_written_bytes() {
+            let _lock = GLOBALS_LOCK.lock().unwrap();
+            let path = temp_path("recording.cast");
+            let mut output = Vec::new();
+
+            recording::start(path.to_str().unwrap(), 80, 24, false).unwrap();
+            write_recorded(&mut output, b"\x1b[1mhi").unwrap();
+            recording::stop().unwrap();
+
+            let cast = std::fs::read_to_string(&path).unwrap();
+            let event = cast.lines().nth(1).unwrap();
+
+            assert_eq!(output, b"\x1b[1mhi");
+            assert!(event.ends_with(", \"o\", \"\\u001b[1mhi\"]"), "{}", event);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crossterm::terminal;
+
+use crate::output;
+
+// Held while the terminal input is read, either by a query or by the event reader
+static INPUT: Mutex<()> = Mutex::new(());
+
+// The number of queries waiting for the input. The event reader stops reading until they
+// are done, otherwise it could consume the replies as key events.
+static PENDING_QUERIES: AtomicUsize = AtomicUsize::new(0);
+
+// Runs `read` with the terminal input locked. Returns None without running it if a query
+// is waiting for the input.
+pub fn with_input<T>(read: impl FnOnce() -> T) -> Option<T> {
+    if PENDING_QUERIES.load(Ordering::Acquire) > 0 {
+        return None;
+    }
+
+    let _input = INPUT.lock().unwrap();
+    Some(read())
+}
+
+// Writes a query to the terminal and reads the reply until `is_complete` returns true or
+// the timeout expires. Returns the bytes read so far.
+//
+// The reply is read from the terminal input directly. The event reader pauses while the
+// query runs, but poll_event/1 doesn't, so this must not be called while events are
+// polled, otherwise the reply will be consumed as key events.
+pub fn query(
+    request: &str,
+    timeout: Duration,
+    is_complete: impl Fn(&[u8]) -> bool,
+) -> io::Result<Vec<u8>> {
+    output::check_input()?;
+
+    PENDING_QUERIES.fetch_add(1, Ordering::AcqRel);
+    let _input = INPUT.lock().unwrap();
+    PENDING_QUERIES.fetch_sub(1, Ordering::AcqRel);
+
+    // The reply is not terminated by a newline, so we need raw mode to read it
+    let was_raw_mode = terminal::is_raw_mode_enabled()?;
+
+    if !was_raw_mode {
+        terminal::enable_raw_mode()?;
+    }
+
+    let result = write_and_read(request, timeout, is_complete);
+
+    if !was_raw_mode {
+        terminal::disable_raw_mode()?;
+    }
+
+    result
+}
+
+#[cfg(unix)]
+fn write_and_read(
+    request: &str,
+    timeout: Duration,
+    is_complete: impl Fn(&[u8]) -> bool,
+) -> io::Result<Vec<u8>> {
+    use std::fs::File;
+    use std::os::fd::AsRawFd;
+    use std::time::Instant;
+
+    // Same as crossterm, read from stdin if it's a TTY, otherwise from /dev/tty
+    let tty = match unsafe { libc::isatty(libc::STDIN_FILENO) } {
+        1 => None,
+        _ => Some(File::open("/dev/tty")?),
+    };
+    let fd = tty
+        .as_ref()
+        .map_or(libc::STDIN_FILENO, |file| file.as_raw_fd());
+
+    let mut writer = output::writer();
+    writer.write_all(request.as_bytes())?;
+    writer.flush()?;
+
+    let deadline = Instant::now() + timeout;
+    let mut reply = Vec::new();
+    let mut buffer = [0u8; 256];
+
+    while !is_complete(&reply) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            break;
+        }
+
+        let mut poll_fd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        match unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as libc::c_int) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => break,
+            _ => {
+                let count = unsafe { libc::read(fd, buffer.as_mut_ptr() as *mut _, buffer.len()) };
+
+                match count {
+                    -1 => return Err(io::Error::last_os_error()),
+                    0 => break,
+                    n => reply.extend_from_slice(&buffer[..n as usize]),
+                }
+            }
+        }
+    }
+
+    Ok(reply)
+}
+
+#[cfg(not(unix))]
+fn write_and_read(
+    _request: &str,
+    _timeout: Duration,
+    _is_complete: impl Fn(&[u8]) -> bool,
+) -> io::Result<Vec<u8>> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// Primary device attributes request. Every terminal replies to it, so we send it after
+// another query to know when to stop waiting, in case the terminal ignores the query.
+pub const DEVICE_ATTRIBUTES_REQUEST: &str = "\x1b[c";
+
+// Returns the CSI sequences in the reply as (parameters, final byte) pairs
+pub fn csi_sequences(reply: &[u8]) -> Vec<(String, u8)> {
+    let mut sequences = Vec::new();
+    let mut index = 0;
+
+    while index + 1 < reply.len() {
+        if reply[index] != 0x1b || reply[index + 1] != b'[' {
+            index += 1;
+            continue;
+        }
+
+        let start = index + 2;
+        let end = reply[start..]
+            .iter()
+            .position(|byte| (0x40..=0x7e).contains(byte))
+            .map(|offset| start + offset);
+
+        match end {
+            Some(end) => {
+                let parameters = String::from_utf8_lossy(&reply[start..end]).into_owned();
+                sequences.push((parameters, reply[end]));
+                index = end + 1;
+            }
+            None => break,
+        }
+    }
+
+    sequences
+}
+
+// Whether the reply contains the primary device attributes reply, i.e. CSI ? ... c
+pub fn has_device_attributes(reply: &[u8]) -> bool {
+    csi_sequences(reply)
+        .iter()
+        .any(|(parameters, final_byte)| *final_byte == b'c' && parameters.starts_with('?'))
+}
+
+// Whether the reply contains the keyboard enhancement flags reply, i.e. CSI ? flags u
+pub fn has_keyboard_enhancement_flags(reply: &[u8]) -> bool {
+    csi_sequences(reply)
+        .iter()
+        .any(|(parameters, final_byte)| *final_byte == b'u' && parameters.starts_with('?'))
+}
+
+// Whether the reply reports DEC mode 2026 as supported. The reply is CSI ? 2026 ; Ps $ y,
+// where Ps is 0 if the mode is not recognized and 4 if it is permanently reset.
+pub fn has_synchronized_output_mode(reply: &[u8]) -> bool {
+    csi_sequences(reply).iter().any(|(parameters, final_byte)| {
+        *final_byte == b'y' && matches!(parameters.as_str(), "?2026;1$" | "?2026;2$" | "?2026;3$")
+    })
+}
+
+// Returns the contents of the OSC sequences in the reply. They are terminated by either
+// BEL or ST (ESC \).
+pub fn osc_sequences(reply: &[u8]) -> Vec<String> {
+    let mut sequences = Vec::new();
+    let mut index = 0;
+
+    while index + 1 < reply.len() {
+        if reply[index] != 0x1b || reply[index + 1] != b']' {
+            index += 1;
+            continue;
+        }
+
+        let start = index + 2;
+        let end = (start..reply.len()).find_map(|end| match reply[end] {
+            0x07 => Some((end, end + 1)),
+            0x1b if reply.get(end + 1) == Some(&b'\\') => Some((end, end + 2)),
+            _ => None,
+        });
+
+        match end {
+            Some((end, next)) => {
+                sequences.push(String::from_utf8_lossy(&reply[start..end]).into_owned());
+                index = next;
+            }
+            None => break,
+        }
+    }
+
+    sequences
+}
+
+#[cfg(test)]
+mod tests {
+    mod csi_sequences {
+        use super::super::*;
+
+        #[test]
+        fn parameters_and_final_byte() {
+            assert_eq!(
+                csi_sequences(b"\x1b[?1u\x1b[?62;22c"),
+                vec![("?1".to_string(), b'u'), ("?62;22".to_string(), b'c')]
+            );
+        }
+
+        #[test]
+        fn incomplete_sequence() {
+            assert_eq!(csi_sequences(b"\x1b[?62;2"), vec![]);
+        }
+    }
+
+    mod has_keyboard_enhancement_flags {
+        use super::super::*;
+
+        #[test]
+        fn flags_reply() {
+            assert!(has_keyboard_enhancement_flags(b"\x1b[?0u\x1b[?62;22c"));
+            assert!(has_keyboard_enhancement_flags(b"\x1b[?15u\x1b[?62;22c"));
+        }
+
+        #[test]
+        fn device_attributes_only() {
+            assert!(!has_keyboard_enhancement_flags(b"\x1b[?62;22c"));
+            assert!(!has_keyboard_enhancement_flags(b"\x1b[1u"));
+        }
+    }
+
+    mod has_synchronized_output_mode {
+        use super::super::*;
+
+        #[test]
+        fn set_or_reset_mode() {
+            assert!(has_synchronized_output_mode(b"\x1b[?2026;1$y\x1b[?62;22c"));
+            assert!(has_synchronized_output_mode(b"\x1b[?2026;2$y\x1b[?62;22c"));
+        }
+
+        #[test]
+        fn unrecognized_or_permanently_reset_mode() {
+            assert!(!has_synchronized_output_mode(b"\x1b[?2026;0$y\x1b[?62;22c"));
+            assert!(!has_synchronized_output_mode(b"\x1b[?2026;4$y\x1b[?62;22c"));
+            assert!(!has_synchronized_output_mode(b"\x1b[?62;22c"));
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+// A session recording in the asciicast v2 format, see
+// https://docs.asciinema.org/manual/asciicast/v2/. The file starts with a header line,
+// followed by one line per event: [time, code, data], where time is the number of seconds
+// since the recording started and code is "o" for output, "i" for input or "r" for a resize.
+struct Recording {
+    file: File,
+    start: Instant,
+    input: bool,
+    // The end of the last output when it's an incomplete UTF-8 sequence. Event data must be
+    // valid UTF-8, and a buffered write can split a character.
+    pending: Vec<u8>,
+}
+
+static RECORDING: Mutex<Option<Recording>> = Mutex::new(None);
+
+// Starts recording to `path`, replacing the file and any active recording. Input events are
+// recorded only if `input` is true, since they might contain secrets, e.g. passwords.
+pub fn start(path: &str, width: u16, height: u16, input: bool) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(header(width, height).as_bytes())?;
+
+    *RECORDING.lock().unwrap() = Some(Recording {
+        file,
+        start: Instant::now(),
+        input,
+        pending: Vec::new(),
+    });
+
+    Ok(())
+}
+
+// Stops the active recording, if any. The file is complete, since events are written as
+// soon as they happen.
+pub fn stop() -> io::Result<()> {
+    match RECORDING.lock().unwrap().take() {
+        Some(mut recording) if !recording.pending.is_empty() => {
+            let data = String::from_utf8_lossy(&recording.pending).into_owned();
+            recording.write_event("o", &data)
+        }
+        _ => Ok(()),
+    }
+}
+
+// Records bytes written to the terminal. Failing to record doesn't fail the write.
+pub fn record_output(bytes: &[u8]) {
+    if let Some(recording) = RECORDING.lock().unwrap().as_mut() {
+        recording.pending.extend_from_slice(bytes);
+        let data = take_utf8(&mut recording.pending);
+
+        if !data.is_empty() {
+            let _ = recording.write_event("o", &data);
+        }
+    }
+}
+
+// Records resizes and, if enabled, the input which produced an event
+pub fn record_event(event: &Event) {
+    if let Some(recording) = RECORDING.lock().unwrap().as_mut() {
+        let _ = match event {
+            Event::Resize(width, height) => {
+                recording.write_event("r", &format!("{}x{}", width, height))
+            }
+            _ if !recording.input => Ok(()),
+            Event::Key(event) => match key_input(event) {
+                Some(data) => recording.write_event("i", &data),
+                None => Ok(()),
+            },
+            Event::Paste(content) => recording.write_event("i", content),
+            _ => Ok(()),
+        };
+    }
+}
+
+impl Recording {
+    fn write_event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        let line = format!("[{:.6}, \"{}\", {}]\n", time, code, json_string(data));
+        self.file.write_all(line.as_bytes())
+    }
+}
+
+fn header(width: u16, height: u16) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    let env = match std::env::var("TERM") {
+        Ok(term) => format!(", \"env\": {{\"TERM\": {}}}", json_string(&term)),
+        Err(_) => String::new(),
+    };
+
+    format!(
+        "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}{}}}\n",
+        width, height, timestamp, env
+    )
+}
+
+// Removes the longest UTF-8 prefix of `bytes` and returns it. An incomplete sequence at
+// the end is kept for the next output, invalid bytes are replaced with U+FFFD.
+fn take_utf8(bytes: &mut Vec<u8>) -> String {
+    let mut data = String::new();
+    let mut rest = bytes.as_slice();
+
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                data.push_str(valid);
+                rest = &[];
+                break;
+            }
+            Err(err) => {
+                let (valid, invalid) = rest.split_at(err.valid_up_to());
+                data.push_str(std::str::from_utf8(valid).unwrap());
+
+                match err.error_len() {
+                    Some(len) => {
+                        data.push(char::REPLACEMENT_CHARACTER);
+                        rest = &invalid[len..];
+                    }
+                    None => {
+                        rest = invalid;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    *bytes = rest.to_vec();
+    data
+}
+
+fn json_string(string: &str) -> String {
+    let mut json = String::with_capacity(string.len() + 2);
+    json.push('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+// The bytes a terminal sends for a key press without keyboard enhancement. Players don't
+// replay input, so keys which can't be encoded this way are not recorded.
+fn key_input(event: &KeyEvent) -> Option<String> {
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+
+    let sequence = match event.code {
+        KeyCode::Char(c) if event.modifiers.contains(KeyModifiers::CONTROL) => {
+            match c.to_ascii_lowercase() {
+                c @ 'a'..='z' => ((c as u8 - b'a' + 1) as char).to_string(),
+                ' ' | '@' => "\0".to_string(),
+                _ => return None,
+            }
+        }
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "\r".to_string(),
+        KeyCode::Tab => "\t".to_string(),
+        KeyCode::BackTab => "\x1b[Z".to_string(),
+        KeyCode::Backspace => "\x7f".to_string(),
+        KeyCode::Esc => "\x1b".to_string(),
+        KeyCode::Up => "\x1b[A".to_string(),
+        KeyCode::Down => "\x1b[B".to_string(),
+        KeyCode::Right => "\x1b[C".to_string(),
+        KeyCode::Left => "\x1b[D".to_string(),
+        KeyCode::Home => "\x1b[H".to_string(),
+        KeyCode::End => "\x1b[F".to_string(),
+        KeyCode::Insert => "\x1b[2~".to_string(),
+        KeyCode::Delete => "\x1b[3~".to_string(),
+        KeyCode::PageUp => "\x1b[5~".to_string(),
+        KeyCode::PageDown => "\x1b[6~".to_string(),
+        KeyCode::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char),
+        KeyCode::F(n @ 5..=12) => {
+            let code = [15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5];
+            format!("\x1b[{}~", code)
+        }
+        _ => return None,
+    };
+
+    // Alt prefixes the key with ESC
+    if event.modifiers.contains(KeyModifiers::ALT) {
+        Some(format!("\x1b{}", sequence))
+    } else {
+        Some(sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod take_utf8 {
+        use super::super::*;
+
+        #[test]
+        fn keeps_incomplete_sequences() {
+            let mut bytes = "aé".as_bytes()[..2].to_vec();
+            assert_eq!(take_utf8(&mut bytes), "a");
+            assert_eq!(bytes, vec![0xc3]);
+
+            bytes.push(0xa9);
+            assert_eq!(take_utf8(&mut bytes), "é");
+            assert!(bytes.is_empty());
+        }
+
+        #[test]
+        fn replaces_invalid_bytes() {
+            let mut bytes = vec![b'a', 0xff, b'b'];
+            assert_eq!(take_utf8(&mut bytes), "a\u{fffd}b");
+            assert!(bytes.is_empty());
+        }
+    }
+
+    mod json_string {
+        use super::super::*;
+
+        #[test]
+        fn escapes_control_characters() {
+            assert_eq!(
+                json_string("\x1b[1m\"a\\b\"\r\n"),
+                "\"\\u001b[1m\\\"a\\\\b\\\"\\r\\n\""
+            );
+        }
+    }
+
+    mod key_input {
+        use super::super::*;
+
+        #[test]
+        fn encodes_legacy_sequences() {
+            let key = |code, modifiers| key_input(&KeyEvent::new(code, modifiers));
+
+            assert_eq!(key(KeyCode::Char('q'), KeyModifiers::NONE).unwrap(), "q");
+            assert_eq!(
+                key(KeyCode::Char('c'), KeyModifiers::CONTROL).unwrap(),
+                "\x03"
+            );
+            assert_eq!(key(KeyCode::Up, KeyModifiers::ALT).unwrap(), "\x1b\x1b[A");
+            assert_eq!(key(KeyCode::F(5), KeyModifiers::NONE).unwrap(), "\x1b[15~");
+            assert_eq!(key(KeyCode::CapsLock, KeyModifiers::NONE), None);
+        }
+    }
+}
@@ -0,0 +1,537 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crossterm::cursor;
+use crossterm::queue;
+use crossterm::style::{
+    Attribute, Color, ContentStyle, Print, SetAttribute, SetAttributes, SetBackgroundColor,
+    SetForegroundColor, SetUnderlineColor,
+};
+use crossterm::terminal::{BeginSynchronizedUpdate, EndSynchronizedUpdate};
+use unicode_width::UnicodeWidthStr;
+
+use crate::viewport;
+
+static SYNCHRONIZED_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+// Where the cursor was moved to, relative to the viewport, and where it was saved. Writes
+// move the cursor back to it, so that it stays where IMEs and screen readers expect the
+// caret.
+static CURSOR_POSITION: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+static SAVED_CURSOR_POSITION: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+
+// When enabled, each write is wrapped in a synchronized update (DEC mode 2026), so that
+// the terminal displays the whole frame at once instead of painting it progressively
+pub fn set_synchronized_output(enabled: bool) {
+    SYNCHRONIZED_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn set_cursor_position(x: usize, y: usize) {
+    *CURSOR_POSITION.lock().unwrap() = Some((x, y));
+}
+
+// Same as the terminal, the position is saved and restored with the cursor
+pub fn save_cursor_position() {
+    *SAVED_CURSOR_POSITION.lock().unwrap() = *CURSOR_POSITION.lock().unwrap();
+}
+
+pub fn restore_cursor_position() {
+    *CURSOR_POSITION.lock().unwrap() = *SAVED_CURSOR_POSITION.lock().unwrap();
+}
+
+// Forgets the positions, e.g. when the screen they were on is left or redrawn
+pub fn reset_cursor_position() {
+    *CURSOR_POSITION.lock().unwrap() = None;
+    *SAVED_CURSOR_POSITION.lock().unwrap() = None;
+}
+
+// A cell which is ready to be written to the terminal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyledCell {
+    pub character: String,
+    pub style: ContentStyle,
+    pub hyperlink: Option<Hyperlink>,
+}
+
+// An OSC 8 hyperlink. Terminals underline cells which share the same id together on hover,
+// even if they are not adjacent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyperlink {
+    pub uri: String,
+    pub id: Option<String>,
+}
+
+impl Hyperlink {
+    // OSC 8 ; params ; URI ST. Control characters would end the sequence early, and the
+    // id can't contain parameter separators, so they are dropped.
+    fn open_sequence(&self) -> String {
+        let uri = self.uri.chars().filter(|c| !c.is_control());
+        let parameters = match &self.id {
+            Some(id) => {
+                let id = id
+                    .chars()
+                    .filter(|c| !c.is_control() && *c != ':' && *c != ';');
+                format!("id={}", id.collect::<String>())
+            }
+            None => String::new(),
+        };
+
+        format!("\x1b]8;{};{}\x1b\\", parameters, uri.collect::<String>())
+    }
+}
+
+const CLOSE_HYPERLINK: &str = "\x1b]8;;\x1b\\";
+
+impl StyledCell {
+    // Empty cells are drawn as an unstyled whitespace
+    pub fn blank() -> Self {
+        StyledCell {
+            character: " ".to_string(),
+            style: ContentStyle::new(),
+            hyperlink: None,
+        }
+    }
+
+    // Whether the cell takes two columns on the terminal, e.g. CJK characters. Frames have
+    // one cell per grapheme, so a wide cell shifts the rest of its row on the terminal.
+    pub fn is_wide(&self) -> bool {
+        self.character.width() > 1
+    }
+
+    // Zero width content, e.g. a lone combining mark, would not move the cursor, so it is
+    // written as a whitespace instead
+    fn text(&self) -> &str {
+        if self.character.width() == 0 {
+            " "
+        } else {
+            &self.character
+        }
+    }
+
+    // Whether terminals agree on the width of the cell. Terminals disagree on the width of
+    // graphemes made of several code points, e.g. emoji sequences, so the cursor position is
+    // unknown after writing them. Ambiguous width characters, e.g. box drawing characters,
+    // are assumed to be narrow like most terminals do outside of CJK locales.
+    fn has_stable_width(&self) -> bool {
+        self.text().chars().nth(1).is_none()
+    }
+}
+
+// Writes the cells at their coordinates. Consecutive cells which share the same style are
+// printed as a single run, and only the SGR changes between runs are emitted. Hyperlinks are
+// opened and closed only when they change between runs. Adjacent runs are written back to
+// back, so that the cells after a wide cell are shifted with it instead of overwriting it.
+// Nothing is written without cells, e.g. when a frame is unchanged.
+pub fn write_cells<'a>(
+    writer: &mut impl Write,
+    cells: impl IntoIterator<Item = (&'a StyledCell, usize, usize)>,
+) -> io::Result<()> {
+    let mut cells = cells.into_iter().peekable();
+
+    if cells.peek().is_none() {
+        return Ok(());
+    }
+
+    let mut painter = Painter::new(
+        writer,
+        SYNCHRONIZED_OUTPUT.load(Ordering::Relaxed),
+        *CURSOR_POSITION.lock().unwrap(),
+    )?;
+
+    for (cell, x, y) in cells {
+        painter.put(cell, x, y)?;
+    }
+
+    painter.finish()
+}
+
+struct Run {
+    text: String,
+    style: ContentStyle,
+    hyperlink: Option<Hyperlink>,
+    x: usize,
+    y: usize,
+    // The number of cells in the run
+    length: usize,
+    // Whether the cursor position is known after writing the run
+    stable: bool,
+}
+
+struct Painter<'w, W: Write> {
+    writer: &'w mut W,
+    // Where the next cell would be written without moving the cursor, if known
+    cursor: Option<(usize, usize)>,
+    // The SGR state of the terminal
+    style: ContentStyle,
+    // The hyperlink which is open
+    hyperlink: Option<Hyperlink>,
+    run: Option<Run>,
+    // Whether the write is wrapped in a synchronized update
+    synchronized: bool,
+    // The screen row of the first line, rows are drawn relative to it
+    origin: usize,
+    // Where the cursor is moved after the write, relative to the first line
+    cursor_position: Option<(usize, usize)>,
+}
+
+impl<'w, W: Write> Painter<'w, W> {
+    // We don't know the SGR state left by previous writes, so start from a clean state
+    fn new(
+        writer: &'w mut W,
+        synchronized: bool,
+        cursor_position: Option<(usize, usize)>,
+    ) -> io::Result<Self> {
+        if synchronized {
+            queue_command(writer, BeginSynchronizedUpdate)?;
+        }
+
+        queue_command(writer, SetAttribute(Attribute::Reset))?;
+
+        Ok(Painter {
+            writer,
+            cursor: None,
+            style: ContentStyle::new(),
+            hyperlink: None,
+            run: None,
+            synchronized,
+            origin: viewport::origin() as usize,
+            cursor_position,
+        })
+    }
+
+    fn put(&mut self, cell: &StyledCell, x: usize, y: usize) -> io::Result<()> {
+        if let Some(run) = &mut self.run {
+            if run.stable
+                && run.style == cell.style
+                && run.hyperlink == cell.hyperlink
+                && run.y == y
+                && run.x + run.length == x
+            {
+                run.text.push_str(cell.text());
+                run.length += 1;
+                run.stable = cell.has_stable_width();
+                return Ok(());
+            }
+        }
+
+        self.flush_run()?;
+        self.run = Some(Run {
+            text: cell.text().to_string(),
+            style: cell.style,
+            hyperlink: cell.hyperlink.clone(),
+            x,
+            y,
+            length: 1,
+            stable: cell.has_stable_width(),
+        });
+
+        Ok(())
+    }
+
+    fn flush_run(&mut self) -> io::Result<()> {
+        let Some(run) = self.run.take() else {
+            return Ok(());
+        };
+
+        if self.cursor != Some((run.x, run.y)) {
+            let row = self.origin + run.y;
+            queue_command(self.writer, cursor::MoveTo(run.x as u16, row as u16))?;
+        }
+
+        self.set_style(run.style)?;
+        self.set_hyperlink(run.hyperlink)?;
+        queue_command(self.writer, Print(&run.text))?;
+
+        // Re-position before the next write if the terminal might not agree on the width
+        self.cursor = run.stable.then_some((run.x + run.length, run.y));
+
+        Ok(())
+    }
+
+    fn set_style(&mut self, mut style: ContentStyle) -> io::Result<()> {
+        // Reset is not a text attribute, it would also reset the colors
+        style.attributes.unset(Attribute::Reset);
+
+        let removed = self.style.attributes ^ (self.style.attributes & style.attributes);
+
+        // Turning attributes off one by one is error prone, e.g. SGR 22 turns off both bold
+        // and dim, so we start over instead
+        if !removed.is_empty() {
+            queue_command(self.writer, SetAttribute(Attribute::Reset))?;
+            self.style = ContentStyle::new();
+        }
+
+        let added = style.attributes ^ (style.attributes & self.style.attributes);
+
+        if !added.is_empty() {
+            queue_command(self.writer, SetAttributes(added))?;
+        }
+
+        if self.style.foreground_color != style.foreground_color {
+            let color = style.foreground_color.unwrap_or(Color::Reset);
+            queue_command(self.writer, SetForegroundColor(color))?;
+        }
+
+        if self.style.background_color != style.background_color {
+            let color = style.background_color.unwrap_or(Color::Reset);
+            queue_command(self.writer, SetBackgroundColor(color))?;
+        }
+
+        if self.style.underline_color != style.underline_color {
+            let color = style.underline_color.unwrap_or(Color::Reset);
+            queue_command(self.writer, SetUnderlineColor(color))?;
+        }
+
+        self.style = style;
+        Ok(())
+    }
+
+    fn set_hyperlink(&mut self, hyperlink: Option<Hyperlink>) -> io::Result<()> {
+        if self.hyperlink == hyperlink {
+            return Ok(());
+        }
+
+        match &hyperlink {
+            Some(hyperlink) => queue_command(self.writer, Print(hyperlink.open_sequence()))?,
+            None => queue_command(self.writer, Print(CLOSE_HYPERLINK))?,
+        }
+
+        self.hyperlink = hyperlink;
+        Ok(())
+    }
+
+    // Leaves the terminal in a clean SGR state and closes the hyperlink, so that other
+    // writes are not styled. The cursor is moved back to where it was moved to, if any.
+    fn finish(mut self) -> io::Result<()> {
+        self.flush_run()?;
+        self.set_hyperlink(None)?;
+
+        if self.style != ContentStyle::new() {
+            queue_command(self.writer, SetAttribute(Attribute::Reset))?;
+        }
+
+        if let Some((x, y)) = self.cursor_position {
+            if self.cursor != Some((x, y)) {
+                let row = (self.origin + y).min(u16::MAX as usize);
+                queue_command(self.writer, cursor::MoveTo(x as u16, row as u16))?;
+            }
+        }
+
+        if self.synchronized {
+            queue_command(self.writer, EndSynchronizedUpdate)?;
+        }
+
+        flush(self.writer)
+    }
+}
+
+pub fn queue_command(
+    writer: &mut impl Write,
+    command: impl crossterm::Command + Clone,
+) -> io::Result<()> {
+    loop {
+        match queue!(writer, command.clone()) {
+            Ok(_) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+pub fn flush(writer: &mut impl Write) -> io::Result<()> {
+    loop {
+        match writer.flush() {
+            Ok(_) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod write_cells {
+        use super::super::*;
+
+        fn cell(character: &str) -> StyledCell {
+            StyledCell {
+                character: character.to_string(),
+                ..StyledCell::blank()
+            }
+        }
+
+        fn write(cells: &[(StyledCell, usize, usize)]) -> String {
+            let mut output = Vec::new();
+            write_cells(&mut output, cells.iter().map(|(cell, x, y)| (cell, *x, *y))).unwrap();
+            String::from_utf8(output).unwrap()
+        }
+
+        #[test]
+        fn writes_nothing_without_cells() {
+            assert_eq!(write(&[]), "");
+        }
+
+        // The layout of "漢字a", one cell per grapheme
+        #[test]
+        fn coalesces_wide_cells() {
+            let output = write(&[(cell("漢"), 0, 0), (cell("字"), 1, 0), (cell("a"), 2, 0)]);
+            assert_eq!(output, "\x1b[0m\x1b[1;1H漢字a");
+        }
+
+        #[test]
+        fn writes_runs_after_wide_cells_back_to_back() {
+            let mut bold = cell("a");
+            bold.style.attributes.set(Attribute::Bold);
+
+            let output = write(&[(cell("漢"), 0, 0), (bold, 1, 0)]);
+            assert_eq!(output, "\x1b[0m\x1b[1;1H漢\x1b[1ma\x1b[0m");
+        }
+
+        #[test]
+        fn coalesces_ambiguous_width_cells() {
+            let row = (0..5).map(|x| (cell("─"), x, 0)).collect::<Vec<_>>();
+            assert_eq!(write(&row), "\x1b[0m\x1b[1;1H─────");
+        }
+
+        #[test]
+        fn repositions_after_unstable_width() {
+            let output = write(&[(cell("e\u{301}"), 0, 0), (cell("a"), 1, 0)]);
+            assert_eq!(output, "\x1b[0m\x1b[1;1He\u{301}\x1b[1;2Ha");
+        }
+
+        #[test]
+        fn groups_cells_with_the_same_hyperlink() {
+            let hyperlink = Some(Hyperlink {
+                uri: "https://example.com".to_string(),
+                id: Some("ticket".to_string()),
+            });
+            let link = |character| StyledCell {
+                hyperlink: hyperlink.clone(),
+                ..cell(character)
+            };
+
+            let output = write(&[(link("a"), 0, 0), (link("b"), 1, 0), (cell("c"), 2, 0)]);
+
+            assert_eq!(
+                output,
+                "\x1b[0m\x1b[1;1H\x1b]8;id=ticket;https://example.com\x1b\\ab\x1b]8;;\x1b\\c"
+            );
+        }
+    }
+
+    mod set_style {
+        use super::super::*;
+
+        fn style(foreground: Option<Color>, attributes: &[Attribute]) -> ContentStyle {
+            let mut style = ContentStyle::new();
+            style.foreground_color = foreground;
+
+            for attribute in attributes {
+                style.attributes.set(*attribute);
+            }
+
+            style
+        }
+
+        // Returns the SGR sequences written to go from one style to the other
+        fn transition(from: ContentStyle, to: ContentStyle) -> String {
+            let mut output = Vec::new();
+            let mut painter = Painter::new(&mut output, false, None).unwrap();
+            painter.set_style(from).unwrap();
+
+            let start = painter.writer.len();
+            painter.set_style(to).unwrap();
+            String::from_utf8(painter.writer[start..].to_vec()).unwrap()
+        }
+
+        #[test]
+        fn unchanged_style() {
+            let bold_red = style(Some(Color::Red), &[Attribute::Bold]);
+            assert_eq!(transition(bold_red, bold_red), "");
+        }
+
+        #[test]
+        fn added_attribute() {
+            assert_eq!(
+                transition(
+                    style(Some(Color::Red), &[Attribute::Bold]),
+                    style(Some(Color::Red), &[Attribute::Bold, Attribute::Italic])
+                ),
+                "\x1b[3m"
+            );
+        }
+
+        #[test]
+        fn removed_attribute_resets_then_restores_colors() {
+            assert_eq!(
+                transition(
+                    style(Some(Color::Red), &[Attribute::Bold, Attribute::Italic]),
+                    style(Some(Color::Red), &[Attribute::Italic])
+                ),
+                "\x1b[0m\x1b[3m\x1b[38;5;9m"
+            );
+        }
+
+        #[test]
+        fn changed_colors() {
+            assert_eq!(
+                transition(style(Some(Color::Red), &[]), style(Some(Color::Blue), &[])),
+                "\x1b[38;5;12m"
+            );
+            assert_eq!(
+                transition(style(Some(Color::Red), &[]), style(None, &[])),
+                "\x1b[39m"
+            );
+        }
+    }
+
+    mod synchronized_output {
+        use super::super::*;
+
+        fn write(synchronized: bool) -> String {
+            let mut output = Vec::new();
+            let mut painter = Painter::new(&mut output, synchronized, None).unwrap();
+            painter.put(&StyledCell::blank(), 0, 0).unwrap();
+            painter.finish().unwrap();
+            String::from_utf8(output).unwrap()
+        }
+
+        #[test]
+        fn wraps_the_write_in_a_synchronized_update() {
+            assert_eq!(write(true), "\x1b[?2026h\x1b[0m\x1b[1;1H \x1b[?2026l");
+        }
+
+        #[test]
+        fn disabled() {
+            assert_eq!(write(false), "\x1b[0m\x1b[1;1H ");
+        }
+    }
+
+    mod cursor_position {
+        use super::super::*;
+
+        fn write(cursor_position: Option<(usize, usize)>) -> String {
+            let mut output = Vec::new();
+            let mut painter = Painter::new(&mut output, false, cursor_position).unwrap();
+            painter.put(&StyledCell::blank(), 0, 0).unwrap();
+            painter.finish().unwrap();
+            String::from_utf8(output).unwrap()
+        }
+
+        #[test]
+        fn moves_the_cursor_back() {
+            assert_eq!(write(Some((3, 1))), "\x1b[0m\x1b[1;1H \x1b[2;4H");
+        }
+
+        #[test]
+        fn already_at_the_position() {
+            assert_eq!(write(Some((1, 0))), "\x1b[0m\x1b[1;1H ");
+        }
+
+        #[test]
+        fn without_position() {
+            assert_eq!(write(None), "\x1b[0m\x1b[1;1H ");
+        }
+    }
+}
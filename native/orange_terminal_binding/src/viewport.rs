@@ -0,0 +1,150 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crossterm::style::Print;
+use crossterm::{cursor, terminal};
+
+use crate::query;
+use crate::render::{flush, queue_command};
+
+// An area of `height` lines starting at the `origin` row of the screen. In inline mode, the
+// frames are drawn in the viewport instead of the whole screen.
+#[derive(Clone, Copy)]
+struct Viewport {
+    origin: u16,
+    height: u16,
+}
+
+static VIEWPORT: Mutex<Option<Viewport>> = Mutex::new(None);
+
+const CURSOR_POSITION_QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The screen row of the first viewport line. Frames are drawn relative to it.
+pub fn origin() -> u16 {
+    VIEWPORT
+        .lock()
+        .unwrap()
+        .map_or(0, |viewport| viewport.origin)
+}
+
+// The viewport height, or None in full screen mode
+pub fn height() -> Option<u16> {
+    VIEWPORT.lock().unwrap().map(|viewport| viewport.height)
+}
+
+// Reserves `height` lines under the cursor. If there are not enough lines below the
+// cursor, the screen is scrolled up to make room. Returns the viewport height, which is
+// at most the terminal height.
+pub fn enter(writer: &mut impl Write, height: u16) -> io::Result<u16> {
+    let (_, terminal_height) = terminal::size()?;
+    let height = viewport_height(height, terminal_height)?;
+    let (_, row) = cursor_position()?;
+
+    // Line feeds on the last line scroll the screen, the same as printing text would
+    queue_command(writer, cursor::MoveToColumn(0))?;
+
+    for _ in 1..height {
+        queue_command(writer, Print("\n"))?;
+    }
+
+    let origin = row.min(terminal_height - height);
+    queue_command(writer, cursor::MoveTo(0, origin))?;
+    queue_command(writer, terminal::Clear(terminal::ClearType::FromCursorDown))?;
+    flush(writer)?;
+
+    *VIEWPORT.lock().unwrap() = Some(Viewport { origin, height });
+    Ok(height)
+}
+
+// Some ptys report a size of 0 when it was never set, there is no room for a viewport then
+fn viewport_height(height: u16, terminal_height: u16) -> io::Result<u16> {
+    match terminal_height {
+        0 => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the terminal height is unknown",
+        )),
+        _ => Ok(height.clamp(1, terminal_height)),
+    }
+}
+
+// Moves the cursor under the viewport, so that the last frame stays on the screen and in
+// the scrollback, then goes back to full screen mode.
+pub fn leave(writer: &mut impl Write) -> io::Result<()> {
+    let Some(viewport) = VIEWPORT.lock().unwrap().take() else {
+        return Ok(());
+    };
+
+    // The viewport is empty if the terminal was resized to 0 lines
+    queue_command(
+        writer,
+        cursor::MoveTo(0, viewport.origin + viewport.height.saturating_sub(1)),
+    )?;
+    queue_command(writer, Print("\r\n"))?;
+    flush(writer)
+}
+
+// Clears the viewport, or the whole screen in full screen mode. The viewport is moved up
+// if the terminal got too small to fit it below its origin.
+pub fn clear(writer: &mut impl Write) -> io::Result<()> {
+    let mut viewport = VIEWPORT.lock().unwrap();
+
+    match viewport.as_mut() {
+        Some(viewport) => {
+            let (_, terminal_height) = terminal::size()?;
+            viewport.height = viewport.height.min(terminal_height);
+            viewport.origin = viewport.origin.min(terminal_height - viewport.height);
+
+            queue_command(writer, cursor::MoveTo(0, viewport.origin))?;
+            queue_command(writer, terminal::Clear(terminal::ClearType::FromCursorDown))?;
+        }
+        None => {
+            queue_command(writer, terminal::Clear(terminal::ClearType::All))?;
+            queue_command(writer, terminal::Clear(terminal::ClearType::Purge))?;
+        }
+    }
+
+    flush(writer)
+}
+
+// Asks the terminal for the cursor position with DSR 6. The reply is CSI row ; column R,
+// both starting from 1.
+fn cursor_position() -> io::Result<(u16, u16)> {
+    let parse = |reply: &[u8]| {
+        query::csi_sequences(reply)
+            .into_iter()
+            .filter(|(_, final_byte)| *final_byte == b'R')
+            .find_map(|(parameters, _)| {
+                let (row, column) = parameters.split_once(';')?;
+                let row = row.parse::<u16>().ok()?;
+                let column = column.parse::<u16>().ok()?;
+                Some((column.saturating_sub(1), row.saturating_sub(1)))
+            })
+    };
+
+    let reply = query::query("\x1b[6n", CURSOR_POSITION_QUERY_TIMEOUT, |reply| {
+        parse(reply).is_some()
+    })?;
+
+    parse(&reply).ok_or_else(|| io::Error::from(io::ErrorKind::TimedOut))
+}
+
+#[cfg(test)]
+mod tests {
+    mod viewport_height {
+        use super::super::*;
+
+        #[test]
+        fn capped_to_the_terminal_height() {
+            assert_eq!(viewport_height(5, 24).unwrap(), 5);
+            assert_eq!(viewport_height(30, 24).unwrap(), 24);
+            assert_eq!(viewport_height(0, 24).unwrap(), 1);
+        }
+
+        #[test]
+        fn unknown_terminal_height() {
+            let err = viewport_height(5, 0).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        }
+    }
+}
@@ -0,0 +1,713 @@
+use std::sync::Mutex;
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+use unicode_width::UnicodeWidthChar;
+use vte::{Params, Parser, Perform};
+
+use crate::color;
+
+// A headless terminal emulator. It parses the bytes written by the binding and keeps what
+// a real terminal would display, so that the output can be tested without a TTY.
+pub struct VirtualTerminal {
+    state: Mutex<State>,
+}
+
+struct State {
+    parser: Parser,
+    screen: Screen,
+}
+
+impl VirtualTerminal {
+    pub fn new(width: usize, height: usize) -> Self {
+        VirtualTerminal {
+            state: Mutex::new(State {
+                parser: Parser::new(),
+                screen: Screen::new(width, height),
+            }),
+        }
+    }
+
+    pub fn write(&self, bytes: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        let State { parser, screen } = &mut *state;
+        parser.advance(screen, bytes);
+    }
+
+    pub fn resize(&self, width: usize, height: usize) {
+        self.state.lock().unwrap().screen.resize(width, height);
+    }
+
+    // Raw mode is a setting of the TTY, not an escape sequence, so it's set by the raw mode
+    // NIFs when the virtual terminal is the output
+    pub fn set_raw_mode(&self, enabled: bool) {
+        self.state.lock().unwrap().screen.raw_mode = enabled;
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        (state.screen.width, state.screen.height)
+    }
+
+    pub fn screen(&self) -> Screen {
+        self.state.lock().unwrap().screen.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenCell {
+    // Empty for the second column of a wide character
+    pub character: String,
+    pub style: ContentStyle,
+    pub hyperlink: Option<String>,
+}
+
+impl ScreenCell {
+    fn blank(background: Option<Color>) -> Self {
+        ScreenCell {
+            character: " ".to_string(),
+            style: ContentStyle {
+                background_color: background,
+                ..ContentStyle::new()
+            },
+            hyperlink: None,
+        }
+    }
+}
+
+type Grid = Vec<Vec<ScreenCell>>;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SavedCursor {
+    x: usize,
+    y: usize,
+    style: ContentStyle,
+}
+
+#[derive(Debug, Clone)]
+pub struct Screen {
+    pub width: usize,
+    pub height: usize,
+    primary: Grid,
+    alternate: Grid,
+    pub alternate_screen: bool,
+    pub cursor: (usize, usize),
+    // The cursor is past the last column, the next character is printed on the next line
+    pending_wrap: bool,
+    saved_cursor: Option<SavedCursor>,
+    // Saved when entering the alternate screen with mode 1049
+    alternate_saved_cursor: Option<SavedCursor>,
+    style: ContentStyle,
+    hyperlink: Option<String>,
+    pub cursor_visible: bool,
+    // The DECSCUSR parameter
+    pub cursor_shape: u8,
+    pub raw_mode: bool,
+    pub bracketed_paste: bool,
+    pub mouse_capture: bool,
+    pub focus_reporting: bool,
+    pub synchronized_output: bool,
+    pub keyboard_enhancement_flags: Vec<u8>,
+    pub title: String,
+    title_stack: Vec<String>,
+}
+
+impl Screen {
+    fn new(width: usize, height: usize) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+
+        Screen {
+            width,
+            height,
+            primary: blank_grid(width, height),
+            alternate: blank_grid(width, height),
+            alternate_screen: false,
+            cursor: (0, 0),
+            pending_wrap: false,
+            saved_cursor: None,
+            alternate_saved_cursor: None,
+            style: ContentStyle::new(),
+            hyperlink: None,
+            cursor_visible: true,
+            cursor_shape: 0,
+            raw_mode: false,
+            bracketed_paste: false,
+            mouse_capture: false,
+            focus_reporting: false,
+            synchronized_output: false,
+            keyboard_enhancement_flags: Vec::new(),
+            title: String::new(),
+            title_stack: Vec::new(),
+        }
+    }
+
+    // The displayed grid
+    pub fn rows(&self) -> &Grid {
+        if self.alternate_screen {
+            &self.alternate
+        } else {
+            &self.primary
+        }
+    }
+
+    fn grid(&mut self) -> &mut Grid {
+        if self.alternate_screen {
+            &mut self.alternate
+        } else {
+            &mut self.primary
+        }
+    }
+
+    // The displayed text, one line per row. Wide characters are followed by their empty
+    // second column, so they are written once.
+    #[cfg(test)]
+    fn text(&self) -> String {
+        self.rows()
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.character.as_str()).collect())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        let (width, height) = (width.max(1), height.max(1));
+
+        for grid in [&mut self.primary, &mut self.alternate] {
+            grid.resize_with(height, || blank_row(width, None));
+
+            for row in grid.iter_mut() {
+                row.resize_with(width, || ScreenCell::blank(None));
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.move_cursor(self.cursor.0, self.cursor.1);
+    }
+
+    // Moves the cursor, clamped to the screen
+    fn move_cursor(&mut self, x: usize, y: usize) {
+        self.cursor = (x.min(self.width - 1), y.min(self.height - 1));
+        self.pending_wrap = false;
+    }
+
+    fn blank(&self) -> ScreenCell {
+        // Erased cells take the current background color, the same as xterm does
+        ScreenCell::blank(self.style.background_color)
+    }
+
+    fn print_char(&mut self, c: char) {
+        let width = c.width().unwrap_or(0);
+
+        // Combining characters are part of the previous cell
+        if width == 0 {
+            let (x, y) = self.cursor;
+            let x = if self.pending_wrap {
+                x
+            } else {
+                x.saturating_sub(1)
+            };
+            let grid = self.grid();
+            let x = match grid[y][x].character.is_empty() {
+                true => x.saturating_sub(1),
+                false => x,
+            };
+            grid[y][x].character.push(c);
+            return;
+        }
+
+        if self.pending_wrap || self.cursor.0 + width > self.width {
+            self.cursor.0 = 0;
+            self.line_feed();
+        }
+
+        let (x, y) = self.cursor;
+        let cell = ScreenCell {
+            character: c.to_string(),
+            style: self.style,
+            hyperlink: self.hyperlink.clone(),
+        };
+
+        self.clear_wide_character(x, y);
+        self.grid()[y][x] = cell.clone();
+
+        if width == 2 && x + 1 < self.width {
+            self.clear_wide_character(x + 1, y);
+            self.grid()[y][x + 1] = ScreenCell {
+                character: String::new(),
+                ..cell
+            };
+        }
+
+        if x + width >= self.width {
+            self.cursor.0 = self.width - 1;
+            self.pending_wrap = true;
+        } else {
+            self.cursor.0 = x + width;
+        }
+    }
+
+    // Overwriting either column of a wide character erases the other one
+    fn clear_wide_character(&mut self, x: usize, y: usize) {
+        let blank = self.blank();
+        let width = self.width;
+        let row = &mut self.grid()[y];
+
+        if row[x].character.is_empty() && x > 0 {
+            row[x - 1] = blank.clone();
+        }
+
+        if x + 1 < width && row[x + 1].character.is_empty() {
+            row[x + 1] = blank;
+        }
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor.1 + 1 >= self.height {
+            self.scroll_up(1);
+        } else {
+            self.cursor.1 += 1;
+        }
+
+        self.pending_wrap = false;
+    }
+
+    fn scroll_up(&mut self, lines: usize) {
+        let blank = self.blank();
+        let width = self.width;
+        let grid = self.grid();
+        let lines = lines.min(grid.len());
+
+        grid.drain(..lines);
+        grid.extend((0..lines).map(|_| vec![blank.clone(); width]));
+    }
+
+    fn scroll_down(&mut self, lines: usize) {
+        let blank = self.blank();
+        let (width, height) = (self.width, self.height);
+        let grid = self.grid();
+        let lines = lines.min(height);
+
+        grid.truncate(height - lines);
+        grid.splice(0..0, (0..lines).map(|_| vec![blank.clone(); width]));
+    }
+
+    fn erase_cells(&mut self, y: usize, columns: std::ops::Range<usize>) {
+        let blank = self.blank();
+
+        for cell in &mut self.grid()[y][columns] {
+            *cell = blank.clone();
+        }
+    }
+
+    fn erase_rows(&mut self, rows: std::ops::Range<usize>) {
+        let width = self.width;
+
+        for y in rows {
+            self.erase_cells(y, 0..width);
+        }
+    }
+
+    // ED
+    fn erase_in_display(&mut self, mode: u16) {
+        let (x, y) = self.cursor;
+        let (width, height) = (self.width, self.height);
+
+        match mode {
+            0 => {
+                self.erase_cells(y, x..width);
+                self.erase_rows(y + 1..height);
+            }
+            1 => {
+                self.erase_rows(0..y);
+                self.erase_cells(y, 0..x + 1);
+            }
+            2 => self.erase_rows(0..height),
+            // Clears the scrollback, which isn't kept
+            _ => (),
+        }
+    }
+
+    // EL
+    fn erase_in_line(&mut self, mode: u16) {
+        let (x, y) = self.cursor;
+        let width = self.width;
+
+        match mode {
+            0 => self.erase_cells(y, x..width),
+            1 => self.erase_cells(y, 0..x + 1),
+            2 => self.erase_cells(y, 0..width),
+            _ => (),
+        }
+    }
+
+    fn save_cursor(&self) -> SavedCursor {
+        SavedCursor {
+            x: self.cursor.0,
+            y: self.cursor.1,
+            style: self.style,
+        }
+    }
+
+    fn restore_cursor(&mut self, saved: Option<SavedCursor>) {
+        let saved = saved.unwrap_or_default();
+        self.style = saved.style;
+        self.move_cursor(saved.x, saved.y);
+    }
+
+    fn set_alternate_screen(&mut self, enabled: bool) {
+        if enabled == self.alternate_screen {
+            return;
+        }
+
+        if enabled {
+            self.alternate_saved_cursor = Some(self.save_cursor());
+            self.alternate = blank_grid(self.width, self.height);
+            self.alternate_screen = true;
+        } else {
+            self.alternate_screen = false;
+            let saved = self.alternate_saved_cursor.take();
+            self.restore_cursor(saved);
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, enabled: bool) {
+        match mode {
+            25 => self.cursor_visible = enabled,
+            47 | 1047 | 1049 => self.set_alternate_screen(enabled),
+            1000 | 1002 | 1003 | 1006 | 1015 => self.mouse_capture = enabled,
+            1004 => self.focus_reporting = enabled,
+            2004 => self.bracketed_paste = enabled,
+            2026 => self.synchronized_output = enabled,
+            _ => (),
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &Params) {
+        // An empty SGR is a reset
+        if params.is_empty() {
+            self.style = ContentStyle::new();
+            return;
+        }
+
+        let mut params = params.iter();
+
+        while let Some(param) = params.next() {
+            let attributes = &mut self.style.attributes;
+
+            match param {
+                [0] => self.style = ContentStyle::new(),
+                [1] => attributes.set(Attribute::Bold),
+                [2] => attributes.set(Attribute::Dim),
+                [3] => attributes.set(Attribute::Italic),
+                [4] | [4, 1] => attributes.set(Attribute::Underlined),
+                [4, 0] => unset_underlines(attributes),
+                [4, 2] | [21] => attributes.set(Attribute::DoubleUnderlined),
+                [4, 3] => attributes.set(Attribute::Undercurled),
+                [4, 4] => attributes.set(Attribute::Underdotted),
+                [4, 5] => attributes.set(Attribute::Underdashed),
+                [5] => attributes.set(Attribute::SlowBlink),
+                [6] => attributes.set(Attribute::RapidBlink),
+                [7] => attributes.set(Attribute::Reverse),
+                [8] => attributes.set(Attribute::Hidden),
+                [9] => attributes.set(Attribute::CrossedOut),
+                [22] => {
+                    attributes.unset(Attribute::Bold);
+                    attributes.unset(Attribute::Dim);
+                }
+                [23] => attributes.unset(Attribute::Italic),
+                [24] => unset_underlines(attributes),
+                [25] => {
+                    attributes.unset(Attribute::SlowBlink);
+                    attributes.unset(Attribute::RapidBlink);
+                }
+                [27] => attributes.unset(Attribute::Reverse),
+                [28] => attributes.unset(Attribute::Hidden),
+                [29] => attributes.unset(Attribute::CrossedOut),
+                [53] => attributes.set(Attribute::OverLined),
+                [55] => attributes.unset(Attribute::OverLined),
+                [n @ 30..=37] => {
+                    self.style.foreground_color = Some(color::ansi_color(*n as u8 - 30))
+                }
+                [n @ 90..=97] => {
+                    self.style.foreground_color = Some(color::ansi_color(*n as u8 - 90 + 8))
+                }
+                [39] => self.style.foreground_color = None,
+                [n @ 40..=47] => {
+                    self.style.background_color = Some(color::ansi_color(*n as u8 - 40))
+                }
+                [n @ 100..=107] => {
+                    self.style.background_color = Some(color::ansi_color(*n as u8 - 100 + 8))
+                }
+                [49] => self.style.background_color = None,
+                [59] => self.style.underline_color = None,
+                [kind @ (38 | 48 | 58), rest @ ..] => {
+                    let color = extended_color(rest, &mut params);
+
+                    match kind {
+                        38 => self.style.foreground_color = color,
+                        48 => self.style.background_color = color,
+                        _ => self.style.underline_color = color,
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+fn unset_underlines(attributes: &mut crossterm::style::Attributes) {
+    for attribute in [
+        Attribute::Underlined,
+        Attribute::DoubleUnderlined,
+        Attribute::Undercurled,
+        Attribute::Underdotted,
+        Attribute::Underdashed,
+    ] {
+        attributes.unset(attribute);
+    }
+}
+
+// The color of SGR 38, 48 and 58, either with subparameters (38:5:n, 38:2::r:g:b) or with
+// the following parameters (38;5;n, 38;2;r;g;b)
+fn extended_color<'a>(
+    subparameters: &[u16],
+    params: &mut impl Iterator<Item = &'a [u16]>,
+) -> Option<Color> {
+    let values = if subparameters.is_empty() {
+        let mut values = vec![params.next()?.first().copied()?];
+        let count = match values[0] {
+            5 => 1,
+            2 => 3,
+            _ => 0,
+        };
+
+        for _ in 0..count {
+            values.push(params.next()?.first().copied()?);
+        }
+
+        values
+    } else {
+        subparameters.to_vec()
+    };
+
+    match values.as_slice() {
+        [5, n] => Some(color::ansi_color(*n as u8)),
+        // The color space id is optional
+        [2, r, g, b] | [2, _, r, g, b] => Some(Color::Rgb {
+            r: *r as u8,
+            g: *g as u8,
+            b: *b as u8,
+        }),
+        _ => None,
+    }
+}
+
+fn blank_row(width: usize, background: Option<Color>) -> Vec<ScreenCell> {
+    vec![ScreenCell::blank(background); width]
+}
+
+fn blank_grid(width: usize, height: usize) -> Grid {
+    vec![blank_row(width, None); height]
+}
+
+// The first parameter, or `default` if it's missing or 0
+fn parameter(params: &Params, index: usize, default: u16) -> u16 {
+    match params.iter().nth(index).and_then(|param| param.first()) {
+        Some(0) | None => default,
+        Some(value) => *value,
+    }
+}
+
+impl Perform for Screen {
+    fn print(&mut self, c: char) {
+        self.print_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' | 0x0b | 0x0c => self.line_feed(),
+            b'\r' => self.move_cursor(0, self.cursor.1),
+            0x08 => self.move_cursor(self.cursor.0.saturating_sub(1), self.cursor.1),
+            b'\t' => self.move_cursor((self.cursor.0 / 8 + 1) * 8, self.cursor.1),
+            _ => (),
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+
+        let (x, y) = self.cursor;
+        let count = |index| parameter(params, index, 1) as usize;
+
+        match (intermediates, action) {
+            ([], 'H' | 'f') => self.move_cursor(count(1) - 1, count(0) - 1),
+            ([], 'A') => self.move_cursor(x, y.saturating_sub(count(0))),
+            ([], 'B') => self.move_cursor(x, y + count(0)),
+            ([], 'C') => self.move_cursor(x + count(0), y),
+            ([], 'D') => self.move_cursor(x.saturating_sub(count(0)), y),
+            ([], 'E') => self.move_cursor(0, y + count(0)),
+            ([], 'F') => self.move_cursor(0, y.saturating_sub(count(0))),
+            ([], 'G') => self.move_cursor(count(0) - 1, y),
+            ([], 'd') => self.move_cursor(x, count(0) - 1),
+            ([], 'J') => self.erase_in_display(parameter(params, 0, 0)),
+            ([], 'K') => self.erase_in_line(parameter(params, 0, 0)),
+            ([], 'S') => self.scroll_up(count(0)),
+            ([], 'T') => self.scroll_down(count(0)),
+            ([], 'm') => self.select_graphic_rendition(params),
+            ([], 's') => self.saved_cursor = Some(self.save_cursor()),
+            ([], 'u') => self.restore_cursor(self.saved_cursor),
+            ([], 't') => match parameter(params, 0, 0) {
+                22 => self.title_stack.push(self.title.clone()),
+                23 => {
+                    if let Some(title) = self.title_stack.pop() {
+                        self.title = title;
+                    }
+                }
+                _ => (),
+            },
+            ([b'?'], 'h') => params
+                .iter()
+                .for_each(|param| self.set_private_mode(param[0], true)),
+            ([b'?'], 'l') => params
+                .iter()
+                .for_each(|param| self.set_private_mode(param[0], false)),
+            ([b' '], 'q') => self.cursor_shape = parameter(params, 0, 0) as u8,
+            ([b'>'], 'u') => self
+                .keyboard_enhancement_flags
+                .push(parameter(params, 0, 0) as u8),
+            ([b'<'], 'u') => {
+                let count = count(0).min(self.keyboard_enhancement_flags.len());
+                let remaining = self.keyboard_enhancement_flags.len() - count;
+                self.keyboard_enhancement_flags.truncate(remaining);
+            }
+            _ => (),
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        match (intermediates, byte) {
+            ([], b'7') => self.saved_cursor = Some(self.save_cursor()),
+            ([], b'8') => self.restore_cursor(self.saved_cursor),
+            // Full reset
+            ([], b'c') => *self = Screen::new(self.width, self.height),
+            _ => (),
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        match params {
+            [b"0" | b"2", title @ ..] => {
+                self.title = String::from_utf8_lossy(&title.join(&b';')).into_owned();
+            }
+            // OSC 8 ; params ; URI, an empty URI closes the hyperlink
+            [b"8", _parameters, uri @ ..] => {
+                let uri = String::from_utf8_lossy(&uri.join(&b';')).into_owned();
+                self.hyperlink = (!uri.is_empty()).then_some(uri);
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod write {
+        use super::super::*;
+
+        fn screen(width: usize, height: usize, bytes: &str) -> Screen {
+            let terminal = VirtualTerminal::new(width, height);
+            terminal.write(bytes.as_bytes());
+            terminal.screen()
+        }
+
+        #[test]
+        fn prints_and_moves_the_cursor() {
+            let screen = screen(4, 3, "ab\x1b[2;2Hc\r\nd");
+
+            assert_eq!(screen.text(), "ab  \n c  \nd   ");
+            assert_eq!(screen.cursor, (1, 2));
+        }
+
+        #[test]
+        fn wraps_and_scrolls() {
+            let screen = screen(3, 2, "abcdefg");
+
+            assert_eq!(screen.text(), "def\ng  ");
+            assert_eq!(screen.cursor, (1, 1));
+        }
+
+        #[test]
+        fn keeps_the_style_of_cells() {
+            let screen = screen(3, 1, "\x1b[1;38;5;9;48;2;1;2;3ma\x1b[0mb");
+            let cell = &screen.rows()[0][0];
+
+            assert!(cell.style.attributes.has(Attribute::Bold));
+            assert_eq!(cell.style.foreground_color, Some(Color::Red));
+            assert_eq!(
+                cell.style.background_color,
+                Some(Color::Rgb { r: 1, g: 2, b: 3 })
+            );
+            assert_eq!(screen.rows()[0][1].style, ContentStyle::new());
+        }
+
+        #[test]
+        fn wide_characters_take_two_cells() {
+            let screen = screen(4, 1, "漢a\x1b[1;2Hb");
+
+            assert_eq!(screen.text(), " ba ");
+        }
+
+        #[test]
+        fn tracks_modes() {
+            let screen = screen(
+                3,
+                2,
+                "a\x1b[?1049h\x1b[?25l\x1b[?2004h\x1b[5 q\x1b]2;Title\x07",
+            );
+
+            assert!(screen.alternate_screen);
+            assert!(!screen.cursor_visible);
+            assert!(screen.bracketed_paste);
+            assert_eq!(screen.cursor_shape, 5);
+            assert_eq!(screen.title, "Title");
+            assert_eq!(screen.text(), "   \n   ");
+        }
+
+        #[test]
+        fn leaving_the_alternate_screen_restores_the_primary_screen() {
+            let screen = screen(3, 1, "a\x1b[?1049hb\x1b[?1049l");
+
+            assert!(!screen.alternate_screen);
+            assert_eq!(screen.text(), "a  ");
+            assert_eq!(screen.cursor, (1, 0));
+        }
+
+        #[test]
+        fn erases_with_the_background_color() {
+            let screen = screen(2, 1, "ab\x1b[41m\x1b[2J");
+
+            assert_eq!(screen.text(), "  ");
+            assert_eq!(
+                screen.rows()[0][0].style.background_color,
+                Some(Color::DarkRed)
+            );
+        }
+
+        #[test]
+        fn keeps_hyperlinks() {
+            let screen = screen(
+                3,
+                1,
+                "\x1b]8;id=a;https://example.com\x1b\\ab\x1b]8;;\x1b\\c",
+            );
+
+            assert_eq!(
+                screen.rows()[0][1].hyperlink.as_deref(),
+                Some("https://example.com")
+            );
+            assert_eq!(screen.rows()[0][2].hyperlink, None);
+        }
+    }
+}
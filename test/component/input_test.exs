@@ -171,6 +171,45 @@ defmodule Orange.Component.InputTest do
     )
   end
 
+  test "inserts pasted text at once" do
+    [snapshot1, snapshot2] =
+      Test.render(__MODULE__.Input,
+        terminal_size: {25, 5},
+        events: [
+          %Terminal.KeyEvent{code: {:char, "f"}},
+          %Terminal.PasteEvent{content: "oo\nbar"},
+          {:wait_and_snapshot, 10},
+          # Submit input
+          %Terminal.KeyEvent{code: :enter},
+          {:wait_and_snapshot, 10},
+          # Quit
+          %Terminal.KeyEvent{code: {:char, "q"}}
+        ]
+      )
+
+    assert_content(
+      snapshot1,
+      """
+      Input: foobar------------
+      Submitted value: --------
+      -------------------------
+      -------------------------
+      -------------------------\
+      """
+    )
+
+    assert_content(
+      snapshot2,
+      """
+      Input: foobar------------
+      Submitted value: foobar--
+      -------------------------
+      -------------------------
+      -------------------------\
+      """
+    )
+  end
+
   test "custom submit_key" do
     [snapshot1, snapshot2, snapshot3] =
       Test.render({__MODULE__.Input, submit_key: {:char, "x"}},
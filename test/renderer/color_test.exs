@@ -29,6 +29,34 @@ defmodule Orange.Renderer.ColorTest do
     end
   end
 
+  describe "extended colors" do
+    test "renders cells with rgb, ansi256 and hex colors" do
+      element =
+        rect style: [
+               width: 10,
+               flex_direction: :column,
+               color: {:rgb, 255, 128, 0},
+               background_color: {:ansi256, 200}
+             ] do
+          "foo"
+          rect style: [color: "#00ff88"] do
+            "bar"
+          end
+        end
+
+      {buffer, _} = Orange.Renderer.render(element, %{width: 15, height: 6})
+
+      Enum.each(0..2, fn x ->
+        assert Buffer.get_color(buffer, x, 0) == {:rgb, 255, 128, 0}
+        assert Buffer.get_background_color(buffer, x, 0) == {:ansi256, 200}
+      end)
+
+      Enum.each(0..2, fn x ->
+        assert Buffer.get_color(buffer, x, 1) == "#00ff88"
+      end)
+    end
+  end
+
   describe "background color" do
     test "renders cells with background color" do
       element =
@@ -27,6 +27,48 @@ defmodule Orange.Renderer.TextTest do
         assert :bold in Buffer.get_modifiers(buffer, x, 1)
       end)
     end
+
+    test "renders text with extended modifiers and underline color" do
+      element =
+        rect style: [
+               width: 10,
+               text_modifiers: [:curly_underline, :reverse],
+               underline_color: :red
+             ] do
+          "foo"
+        end
+
+      {buffer, _} = Orange.Renderer.render(element, %{width: 15, height: 6})
+
+      Enum.each(0..2, fn x ->
+        assert :curly_underline in Buffer.get_modifiers(buffer, x, 0)
+        assert :reverse in Buffer.get_modifiers(buffer, x, 0)
+        assert Buffer.get_underline_color(buffer, x, 0) == :red
+      end)
+    end
+  end
+
+  describe "hyperlink" do
+    test "renders text with hyperlink" do
+      element =
+        rect style: [width: 10] do
+          rect style: [hyperlink: {"https://example.com", "example"}] do
+            "foo"
+          end
+
+          "bar"
+        end
+
+      {buffer, _} = Orange.Renderer.render(element, %{width: 15, height: 6})
+
+      Enum.each(0..2, fn x ->
+        assert Buffer.get_cell(buffer, {x, 0}).hyperlink == {"https://example.com", "example"}
+      end)
+
+      Enum.each(3..5, fn x ->
+        assert Buffer.get_cell(buffer, {x, 0}).hyperlink == nil
+      end)
+    end
   end
 
   describe "line wrap" do
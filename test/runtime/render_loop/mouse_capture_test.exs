@@ -0,0 +1,51 @@
+defmodule Orange.Runtime.RenderLoop.MouseCaptureTest do
+  use ExUnit.Case
+
+  alias Orange.Test
+  alias Orange.Terminal.VirtualTerminal
+
+  setup do
+    on_exit(fn -> Application.delete_env(:orange, :mouse_capture) end)
+  end
+
+  test "captures the mouse when enabled and releases it on exit" do
+    Application.put_env(:orange, :mouse_capture, true)
+
+    [snapshot] =
+      Test.render(__MODULE__.Example,
+        terminal_size: {8, 1},
+        virtual_terminal: true,
+        events: [{:wait_and_snapshot, 20}]
+      )
+
+    assert snapshot.screen.mouse_capture
+    refute VirtualTerminal.screen(Test.MockTerminal.virtual_terminal()).mouse_capture
+  end
+
+  test "doesn't capture the mouse by default" do
+    [snapshot] =
+      Test.render(__MODULE__.Example,
+        terminal_size: {8, 1},
+        virtual_terminal: true,
+        events: [{:wait_and_snapshot, 20}]
+      )
+
+    refute snapshot.screen.mouse_capture
+  end
+
+  defmodule Example do
+    @behaviour Orange.Component
+
+    import Orange.Macro
+
+    @impl true
+    def init(_attrs), do: %{state: nil, events_subscription: false}
+
+    @impl true
+    def render(_state, _attrs, _update) do
+      rect do
+        "Hello"
+      end
+    end
+  end
+end
@@ -0,0 +1,47 @@
+defmodule Orange.Runtime.RenderLoop.ResumeTest do
+  use ExUnit.Case
+
+  import Orange.Test.Assertions
+
+  alias Orange.{Test, Terminal}
+  alias Orange.Terminal.VirtualTerminal
+
+  test "redraws the whole screen on resume" do
+    # The screen content is lost while suspended, e.g. another program drew on it
+    overwrite_screen = fn ->
+      VirtualTerminal.write(Test.MockTerminal.virtual_terminal(), "\e[2;1Hjunk")
+    end
+
+    [snapshot1, snapshot2] =
+      Test.render(__MODULE__.Example,
+        terminal_size: {8, 2},
+        virtual_terminal: true,
+        events: [
+          {:wait, 20},
+          {:function, overwrite_screen},
+          {:wait_and_snapshot, 20},
+          %Terminal.ResumeEvent{},
+          {:wait_and_snapshot, 20}
+        ]
+      )
+
+    assert_screen(snapshot1, "Hello   \njunk    ")
+    assert_screen(snapshot2, "Hello   \n        ")
+  end
+
+  defmodule Example do
+    @behaviour Orange.Component
+
+    import Orange.Macro
+
+    @impl true
+    def init(_attrs), do: %{state: nil, events_subscription: false}
+
+    @impl true
+    def render(_state, _attrs, _update) do
+      rect do
+        "Hello"
+      end
+    end
+  end
+end
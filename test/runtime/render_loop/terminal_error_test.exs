@@ -0,0 +1,54 @@
+defmodule Orange.Runtime.RenderLoop.TerminalErrorTest do
+  use ExUnit.Case
+
+  import ExUnit.CaptureLog
+
+  alias Orange.Test.MockTerminal
+
+  setup do
+    on_exit(fn -> Application.delete_env(:orange, :terminal) end)
+  end
+
+  test "stops the runtime when the frame can't be drawn" do
+    Application.put_env(:orange, :terminal, __MODULE__.ClosedTerminal)
+    MockTerminal.setup(terminal_size: {8, 1})
+
+    log =
+      capture_log(fn ->
+        {:ok, pid} = Orange.Runtime.start(__MODULE__.Example)
+        ref = Process.monitor(pid)
+
+        assert_receive {:DOWN, ^ref, :process, _pid, _reason}, 1000
+      end)
+
+    assert log =~ "Terminal error: :broken_pipe"
+  end
+
+  # The mock terminal, except that the output is a closed pipe
+  defmodule ClosedTerminal do
+    for {name, arity} <- MockTerminal.__info__(:functions), name != :present do
+      arguments = Macro.generate_arguments(arity, __MODULE__)
+
+      def unquote(name)(unquote_splicing(arguments)),
+        do: MockTerminal.unquote(name)(unquote_splicing(arguments))
+    end
+
+    def present(_frame_buffer, _buffer), do: {:error, :broken_pipe}
+  end
+
+  defmodule Example do
+    @behaviour Orange.Component
+
+    import Orange.Macro
+
+    @impl true
+    def init(_attrs), do: %{state: nil, events_subscription: false}
+
+    @impl true
+    def render(_state, _attrs, _update) do
+      rect do
+        "Hello"
+      end
+    end
+  end
+end
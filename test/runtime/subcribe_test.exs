@@ -59,6 +59,48 @@ defmodule Orange.Runtime.SubscribeTest do
     )
   end
 
+  test "subscribed components receive mouse events" do
+    [snapshot1, snapshot2] =
+      Test.render({__MODULE__.Counter, highlighted: true, events_subscription: true},
+        terminal_size: {20, 6},
+        events: [
+          # Increase by two
+          %Terminal.MouseEvent{kind: :scroll_up, column: 2, row: 1},
+          %Terminal.MouseEvent{kind: :scroll_up, column: 2, row: 1},
+          {:wait_and_snapshot, 10},
+          # Decrease by one
+          %Terminal.MouseEvent{kind: :scroll_down, column: 2, row: 1},
+          {:wait_and_snapshot, 10},
+          # Quit
+          %Terminal.KeyEvent{code: {:char, "q"}}
+        ]
+      )
+
+    assert_content(
+      snapshot1,
+      """
+      ┌─────────────┐-----
+      │Counter: 2---│-----
+      └─────────────┘-----
+      --------------------
+      --------------------
+      --------------------\
+      """
+    )
+
+    assert_content(
+      snapshot2,
+      """
+      ┌─────────────┐-----
+      │Counter: 1---│-----
+      └─────────────┘-----
+      --------------------
+      --------------------
+      --------------------\
+      """
+    )
+  end
+
   test "unsubcribed components don't receive events" do
     [snapshot1, snapshot2, snapshot3, snapshot4] =
       Test.render(__MODULE__.CounterWrapper,
@@ -216,6 +258,12 @@ defmodule Orange.Runtime.SubscribeTest do
         %Terminal.KeyEvent{code: :down} ->
           {:update, state - 1}
 
+        %Terminal.MouseEvent{kind: :scroll_up} ->
+          {:update, state + 1}
+
+        %Terminal.MouseEvent{kind: :scroll_down} ->
+          {:update, state - 1}
+
         %Terminal.KeyEvent{code: {:char, "q"}} ->
           Orange.stop()
           :noop
@@ -0,0 +1,106 @@
+defmodule Orange.TerminalTest do
+  use ExUnit.Case
+
+  alias Orange.Renderer.Buffer
+  alias Orange.Terminal
+  alias Orange.Terminal.VirtualTerminal
+
+  describe "render_to_binary/1" do
+    test "returns the escape sequences draw would write" do
+      buffer =
+        Buffer.new({2, 2})
+        |> Buffer.write_string({0, 0}, "hi", :horizontal, color: :red, text_modifiers: [:bold])
+        |> Buffer.write_string({0, 1}, "!", :horizontal)
+
+      assert Terminal.render_to_binary(buffer) ==
+               "\e[0m\e[1;1H\e[1m\e[38;5;9mhi\e[2;1H\e[0m!"
+    end
+
+    test "skips empty cells" do
+      buffer = Buffer.write_string(Buffer.new({3, 1}), {2, 0}, "a", :horizontal)
+
+      assert Terminal.render_to_binary(buffer) == "\e[0m\e[1;3Ha"
+    end
+
+    test "writes every cell after a wide character" do
+      buffer = Buffer.write_string(Buffer.new({3, 1}), {0, 0}, "漢字a", :horizontal)
+
+      assert Terminal.render_to_binary(buffer) == "\e[0m\e[1;1H漢字a"
+    end
+
+    test "raises on unknown text modifiers" do
+      buffer =
+        Buffer.write_string(Buffer.new({1, 1}), {0, 0}, "a", :horizontal, text_modifiers: [:blod])
+
+      assert_raise ErlangError, ~r/invalid_text_modifier/, fn ->
+        Terminal.render_to_binary(buffer)
+      end
+    end
+
+    test "raises on invalid colors" do
+      for color <- [:orange, {:rgb, 256, 0, 0}, {:ansi256, -1}, "#ff00"] do
+        buffer = Buffer.write_string(Buffer.new({1, 1}), {0, 0}, "a", :horizontal, color: color)
+
+        assert_raise ErlangError, ~r/invalid_color/, fn ->
+          Terminal.render_to_binary(buffer)
+        end
+      end
+    end
+  end
+
+  describe "VirtualTerminal" do
+    test "displays the output of the binding" do
+      buffer =
+        Buffer.new({4, 2})
+        |> Buffer.write_string({0, 0}, "ab", :horizontal, color: :red, text_modifiers: [:bold])
+        |> Buffer.write_string({1, 1}, "c", :horizontal, background_color: {:rgb, 1, 2, 3})
+
+      terminal = VirtualTerminal.new({4, 2})
+      VirtualTerminal.write(terminal, Terminal.render_to_binary(buffer))
+      screen = VirtualTerminal.screen(terminal)
+
+      assert VirtualTerminal.Screen.to_string(screen) == "ab  \n c  "
+      assert screen.cursor == {2, 1}
+
+      assert %VirtualTerminal.Cell{character: "a", foreground: :red, modifiers: [:bold]} =
+               VirtualTerminal.Screen.get_cell(screen, 0, 0)
+
+      assert %VirtualTerminal.Cell{background: {:rgb, 1, 2, 3}} =
+               VirtualTerminal.Screen.get_cell(screen, 1, 1)
+    end
+
+    test "tracks the terminal modes" do
+      terminal = VirtualTerminal.new({4, 2})
+      VirtualTerminal.write(terminal, "\e[?1049h\e[?25l\e[?2004h\e[>1u")
+      screen = VirtualTerminal.screen(terminal)
+
+      assert screen.alternate_screen
+      refute screen.cursor_visible
+      assert screen.bracketed_paste
+      assert screen.keyboard_enhancement_flags == [:disambiguate_escape_codes]
+    end
+  end
+
+  describe "start_recording/2" do
+    @tag :tmp_dir
+    test "records the output to an asciicast file", %{tmp_dir: tmp_dir} do
+      path = Path.join(tmp_dir, "session.cast")
+      buffer = Buffer.write_string(Buffer.new({3, 1}), {2, 0}, "a", :horizontal)
+
+      :ok = Terminal.set_output({:virtual, VirtualTerminal.new({3, 1})})
+
+      try do
+        :ok = Terminal.start_recording(path)
+        :ok = Terminal.draw(buffer)
+        :ok = Terminal.stop_recording()
+      after
+        Terminal.set_output(:stdout)
+      end
+
+      [header, event] = path |> File.read!() |> String.split("\n", trim: true)
+
+      assert header =~ ~r/^\{"version": 2, "width": 3, "height": 1, "timestamp": \d+/
+      assert event =~ ~r/^\[\d+\.\d{6}, "o", "\\u001b\[0m\\u001b\[1;3Ha"\]$/
+    end
+  end
+end
@@ -22,6 +22,23 @@ defmodule Orange.Test.AssertionsTest do
     end
   end
 
+  describe "screen assertions" do
+    test "assert_screen matches the virtual terminal screen" do
+      terminal = Orange.Terminal.VirtualTerminal.new({3, 2})
+      Orange.Terminal.VirtualTerminal.write(terminal, "ab\r\nc")
+
+      snapshot = %Snapshot{screen: Orange.Terminal.VirtualTerminal.screen(terminal)}
+
+      expected =
+        """
+        ab\s
+        c  \
+        """
+
+      Assertions.assert_screen(snapshot, expected)
+    end
+  end
+
   describe "color assertions" do
     test "assert_color with single coordinate" do
       buffer = Buffer.new({2, 2})
@@ -0,0 +1,57 @@
+defmodule Orange.Test.RenderTest do
+  use ExUnit.Case
+
+  import Orange.Test.Assertions
+
+  alias Orange.{Test, Terminal}
+
+  describe "virtual_terminal option" do
+    test "displays what a real terminal would" do
+      [snapshot1, snapshot2] =
+        Test.render(__MODULE__.Example,
+          terminal_size: {12, 2},
+          virtual_terminal: true,
+          events: [
+            {:wait_and_snapshot, 20},
+            %Terminal.KeyEvent{code: {:char, "x"}},
+            {:wait_and_snapshot, 20}
+          ]
+        )
+
+      assert_screen(snapshot1, "Hello world \n            ")
+
+      # The cells which became empty are erased
+      assert_screen(snapshot2, "Hi          \n            ")
+    end
+
+    test "requires the terminal size" do
+      assert_raise ArgumentError, fn ->
+        Test.render(__MODULE__.Example, virtual_terminal: true)
+      end
+    end
+  end
+
+  defmodule Example do
+    @behaviour Orange.Component
+
+    import Orange.Macro
+
+    @impl true
+    def init(_attrs), do: %{state: "Hello world", events_subscription: true}
+
+    @impl true
+    def handle_event(event, state, _attrs, _update) do
+      case event do
+        %Terminal.KeyEvent{code: {:char, "x"}} -> "Hi"
+        _ -> state
+      end
+    end
+
+    @impl true
+    def render(state, _attrs, _update) do
+      rect do
+        state
+      end
+    end
+  end
+end